@@ -0,0 +1,392 @@
+//! `txengine::balances`
+//!
+//! Balances of all clients, keyed by client and currency, as held by an
+//! engine or read back from a balances file written by an earlier run. Two
+//! sets of balances can be reconciled against each other, e.g. the balances
+//! of the engine against an independent file from a partner:
+//!
+//! |Difference|Found when|
+//! |-|-|
+//! |`Missing`|an expected balance is not in the actual ones|
+//! |`Extra`|an actual balance is not in the expected ones|
+//! |`Mismatch`|a field of a balance differs, one for each field|
+//!
+//! Amounts are compared at the minor units of their currency, and differ
+//! when they are more than the tolerance apart. A balance read from a file
+//! keeps its total as written, so a total that is not available + held is
+//! reported as a `Mismatch` of the total rather than failing the read. `diff` instead gives how much
+//! each balance moved between two sets of balances, e.g. before and after a
+//! change of policy, along with a `Summary`.
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::transaction::{ClientId, TransactionEngine};
+use crate::transaction::amount::Amount;
+use crate::transaction::currency::Currency;
+use crate::transaction::ledger::ClientBalance;
+
+/// `Balances` are the balances of all clients in each currency
+pub type Balances = BTreeMap<(ClientId, Currency), Balance>;
+
+/// `Balance` is the balance of a client in a currency. Unlike a
+/// `ClientBalance` the total is kept rather than derived, as a balances file
+/// may have a total that is not available + held
+#[derive(Debug, Clone, PartialEq)]
+pub struct Balance {
+    client : ClientId,
+    currency : Currency,
+    available : Amount,
+    held : Amount,
+    total : Amount,
+    locked : bool,
+}
+
+impl Balance {
+    /// a zero balance of the client in the currency
+    pub fn new(client : ClientId, currency : Currency) -> Self {
+        let zero = Amount::new(0.0);
+        Balance { client, currency, available : zero, held : zero, total : zero, locked : false }
+    }
+
+    pub fn client(&self) -> ClientId {
+        self.client
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn available(&self) -> Amount {
+        self.available
+    }
+
+    pub fn held(&self) -> Amount {
+        self.held
+    }
+
+    pub fn total(&self) -> Amount {
+        self.total
+    }
+
+    pub fn locked(&self) -> bool {
+        self.locked
+    }
+}
+
+impl From<&ClientBalance> for Balance {
+    fn from(balance : &ClientBalance) -> Self {
+        Balance {
+            client : balance.client(),
+            currency : balance.currency(),
+            available : balance.available(),
+            held : balance.held(),
+            total : balance.total(),
+            locked : balance.locked(),
+        }
+    }
+}
+
+/// a line of a balances file, as written for a `ClientBalance`
+#[derive(Deserialize)]
+struct BalanceRecord {
+    client : ClientId,
+    #[serde(default)]
+    currency : Currency,
+    available : f32,
+    held : f32,
+    total : f32,
+    locked : bool,
+}
+
+impl From<BalanceRecord> for Balance {
+    fn from(record : BalanceRecord) -> Self {
+        Balance {
+            client : record.client,
+            currency : record.currency,
+            available : Amount::new(record.available),
+            held : Amount::new(record.held),
+            total : Amount::new(record.total),
+            locked : record.locked,
+        }
+    }
+}
+
+/// `from_engine` copies the balances of every client of the engine
+pub fn from_engine(engine : &TransactionEngine) -> Balances {
+    engine.iter()
+        .map(|balance| ((balance.client(), balance.currency()), Balance::from(balance)))
+        .collect()
+}
+
+/// `from_csv` reads a balances file with the columns written by the engine,
+/// `client,currency,available,held,total,locked`, the currency can be left
+/// empty for the default currency. A client can only have one line for each
+/// currency
+pub fn from_csv(path : &str) -> crate::Result<Balances> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(path)?;
+
+    let mut balances = Balances::new();
+    for record in reader.deserialize::<BalanceRecord>() {
+        let balance = Balance::from(record?);
+        let key = (balance.client(), balance.currency());
+        if balances.insert(key, balance).is_some() {
+            return Err(format!("Client {} has more than one {} balance in {}", key.0, display_currency(key.1), path).into());
+        }
+    }
+
+    Ok(balances)
+}
+
+/// `Field` is a field of a balance that is compared
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Available,
+    Held,
+    Total,
+    Locked,
+}
+
+impl Field {
+    pub const ALL : [Field; 4] = [Field::Available, Field::Held, Field::Total, Field::Locked];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Field::Available => "available",
+            Field::Held => "held",
+            Field::Total => "total",
+            Field::Locked => "locked",
+        }
+    }
+
+    /// the value of the field, as it is written to a balances file
+    pub fn value(&self, balance : &Balance) -> String {
+        match self {
+            Field::Available => balance.available().display_in(balance.currency()),
+            Field::Held => balance.held().display_in(balance.currency()),
+            Field::Total => balance.total().display_in(balance.currency()),
+            Field::Locked => balance.locked().to_string(),
+        }
+    }
+
+    fn amount(&self, balance : &Balance) -> Option<Amount> {
+        match self {
+            Field::Available => Some(balance.available()),
+            Field::Held => Some(balance.held()),
+            Field::Total => Some(balance.total()),
+            Field::Locked => None,
+        }
+    }
+
+    /// whether the field differs between the two balances by more than the
+    /// tolerance
+    fn differs(&self, expected : &Balance, actual : &Balance, tolerance : f32) -> bool {
+        match (self.amount(expected), self.amount(actual)) {
+            (Some(expected_amount), Some(actual_amount)) => {
+                (*(expected_amount - actual_amount).round_to(expected.currency())).abs() > tolerance
+            },
+            _ => expected.locked() != actual.locked(),
+        }
+    }
+}
+
+/// `Difference` is a difference found by `reconcile`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Difference {
+    Missing(Balance),
+    Extra(Balance),
+    Mismatch { field : Field, expected : Balance, actual : Balance },
+}
+
+impl Difference {
+    /// the client and currency of the balance that differs
+    pub fn key(&self) -> (ClientId, Currency) {
+        let balance = match self {
+            Difference::Missing(balance) | Difference::Extra(balance) => balance,
+            Difference::Mismatch { expected, .. } => expected,
+        };
+        (balance.client(), balance.currency())
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (client, currency) = self.key();
+        match self {
+            Difference::Missing(_) => write!(f, "Client {} {} balance is missing", client, display_currency(currency)),
+            Difference::Extra(_) => write!(f, "Client {} {} balance is not expected", client, display_currency(currency)),
+            Difference::Mismatch { field, expected, actual } => {
+                write!(f, "Client {} {} {} should be {} but is {}", client, display_currency(currency), field.name(),
+                    field.value(expected), field.value(actual))
+            },
+        }
+    }
+}
+
+/// `reconcile` compares the actual balances against the expected ones and
+/// returns the differences, ordered by client and currency
+pub fn reconcile(expected : &Balances, actual : &Balances, tolerance : f32) -> Vec<Difference> {
+    let mut differences = Vec::new();
+
+    for (key, expected_balance) in expected {
+        let Some(actual_balance) = actual.get(key) else {
+            differences.push(Difference::Missing(expected_balance.clone()));
+            continue;
+        };
+
+        for field in Field::ALL {
+            if field.differs(expected_balance, actual_balance, tolerance) {
+                differences.push(Difference::Mismatch {
+                    field,
+                    expected : expected_balance.clone(),
+                    actual : actual_balance.clone(),
+                });
+            }
+        }
+    }
+
+    for (key, actual_balance) in actual {
+        if !expected.contains_key(key) {
+            differences.push(Difference::Extra(actual_balance.clone()));
+        }
+    }
+
+    differences.sort_by_key(Difference::key);
+    differences
+}
+
+/// `Change` is how a balance changed between two sets of balances
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl Change {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Change::Added => "added",
+            Change::Removed => "removed",
+            Change::Changed => "changed",
+        }
+    }
+}
+
+/// `Delta` is how much a balance moved, a balance that was added or removed
+/// moved from or to zero
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delta {
+    pub client : ClientId,
+    pub currency : Currency,
+    pub change : Change,
+    pub available : Amount,
+    pub held : Amount,
+    pub total : Amount,
+    pub was_locked : bool,
+    pub locked : bool,
+}
+
+impl Delta {
+    fn new(change : Change, before : &Balance, after : &Balance) -> Self {
+        Delta {
+            client : before.client(),
+            currency : before.currency(),
+            change,
+            available : (after.available() - before.available()).round_to(before.currency()),
+            held : (after.held() - before.held()).round_to(before.currency()),
+            total : (after.total() - before.total()).round_to(before.currency()),
+            was_locked : before.locked(),
+            locked : after.locked(),
+        }
+    }
+}
+
+/// `diff` returns the balances that moved by more than the tolerance, or
+/// were locked or unlocked, ordered by client and currency
+pub fn diff(before : &Balances, after : &Balances, tolerance : f32) -> Vec<Delta> {
+    let zero = |balance : &Balance| Balance::new(balance.client(), balance.currency());
+
+    let mut deltas = Vec::new();
+    for (key, before_balance) in before {
+        let delta = match after.get(key) {
+            Some(after_balance) => {
+                let moved = Field::ALL.iter().any(|field| field.differs(before_balance, after_balance, tolerance));
+                if !moved {
+                    continue;
+                }
+                Delta::new(Change::Changed, before_balance, after_balance)
+            },
+            None => Delta::new(Change::Removed, before_balance, &zero(before_balance)),
+        };
+        deltas.push(delta);
+    }
+
+    for (key, after_balance) in after {
+        if !before.contains_key(key) {
+            deltas.push(Delta::new(Change::Added, &zero(after_balance), after_balance));
+        }
+    }
+
+    deltas.sort_by_key(|delta| (delta.client, delta.currency));
+    deltas
+}
+
+/// `Net` is the sum of the deltas in a currency
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Net {
+    pub available : Amount,
+    pub held : Amount,
+    pub total : Amount,
+}
+
+/// `Summary` counts the balances compared by `diff` and how they moved
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    /// balances in either set
+    pub compared : usize,
+    pub unchanged : usize,
+    pub changed : usize,
+    pub added : usize,
+    pub removed : usize,
+    /// balances in both sets that were locked, or unlocked, in between
+    pub locked : usize,
+    pub unlocked : usize,
+    pub net : BTreeMap<Currency, Net>,
+}
+
+impl Summary {
+    pub fn new(before : &Balances, after : &Balances, deltas : &[Delta]) -> Self {
+        let compared = before.len() + after.keys().filter(|key| !before.contains_key(key)).count();
+        let count = |change : Change| deltas.iter().filter(|delta| delta.change == change).count();
+        let changed = deltas.iter().filter(|delta| delta.change == Change::Changed);
+
+        let mut net = BTreeMap::new();
+        for delta in deltas {
+            let zero = Amount::new(0.0);
+            let sum = net.entry(delta.currency).or_insert(Net { available : zero, held : zero, total : zero });
+            sum.available += delta.available;
+            sum.held += delta.held;
+            sum.total += delta.total;
+        }
+
+        Summary {
+            compared,
+            unchanged : compared - deltas.len(),
+            changed : count(Change::Changed),
+            added : count(Change::Added),
+            removed : count(Change::Removed),
+            locked : changed.clone().filter(|delta| delta.locked && !delta.was_locked).count(),
+            unlocked : changed.filter(|delta| !delta.locked && delta.was_locked).count(),
+            net,
+        }
+    }
+}
+
+/// the code of the currency, or `default` for the default currency
+fn display_currency(currency : Currency) -> String {
+    if currency.is_default() { "default".to_string() } else { currency.to_string() }
+}
@@ -0,0 +1,276 @@
+//! `txengine::http::HttpServer`
+//!
+//! A REST API over a shared `TransactionEngine`. Only available with the
+//! `http` feature.
+//!
+//! |Endpoint|Description|
+//! |-|-|
+//! |`POST /transactions`|Applies a json transaction|
+//! |`GET /clients`|Balances of all clients, one for each currency|
+//! |`GET /clients/{id}`|Balance of a client, `?currency=EUR` for another currency|
+//! |`GET /transactions/{tx}`|A past deposit / withdrawal|
+//! |`GET /openapi.json`|OpenAPI description of the above|
+//!
+//! A submission with an `Idempotency-Key` header is applied at most once.
+//! Sending the same transaction again with the same key returns the response
+//! of the first submission without applying it twice, while sending a
+//! different transaction with the same key is a conflict. Only applied
+//! transactions are remembered, so a rejected one can be retried with the
+//! same key, and only the last `MAX_SUBMISSIONS` of them.
+//!
+//! Without the header a deposit or withdrawal is applied once for each tx
+//! id: sending it again replies `200` with the client's balance without
+//! applying it, while a different deposit or withdrawal with a known tx id
+//! is a conflict. Disputes, resolves and the like can only be made
+//! idempotent with the header, as e.g. a dispute can rightly be sent again
+//! after a resolve.
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::transaction::{ClientId, Transaction, TransactionEngine, TransactionId, TransactionType};
+use crate::transaction::amount::Amount;
+use crate::transaction::currency::Currency;
+use crate::transaction::ledger::{LedgerError, RecordKind};
+
+const OPENAPI : &str = include_str!("../openapi.json");
+
+pub struct HttpServer {
+    server : Server,
+    engine : Arc<Mutex<TransactionEngine>>,
+    submissions : Mutex<Submissions>,
+}
+
+/// the number of applied submissions that are remembered by their key
+pub const MAX_SUBMISSIONS : usize = 10_000;
+
+/// `Submission` remembers the response to an applied transaction so that
+/// a retry gets the same response
+struct Submission {
+    transaction : Transaction,
+    body : String,
+}
+
+/// `Submissions` are the applied submissions by their idempotency key,
+/// forgetting the oldest ones past `MAX_SUBMISSIONS`
+#[derive(Default)]
+struct Submissions {
+    by_key : HashMap<String, Submission>,
+    order : VecDeque<String>,
+}
+
+impl Submissions {
+    fn insert(&mut self, key : String, submission : Submission) {
+        if self.order.len() == MAX_SUBMISSIONS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.by_key.remove(&oldest);
+            }
+        }
+        self.order.push_back(key.clone());
+        self.by_key.insert(key, submission);
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error : &'a str,
+}
+
+#[derive(Serialize)]
+struct TransactionBody {
+    tx : TransactionId,
+    client : ClientId,
+    amount : Amount,
+}
+
+impl HttpServer {
+    pub fn bind(addr : &str, engine : Arc<Mutex<TransactionEngine>>) -> crate::Result<Self> {
+        Ok(
+            HttpServer {
+                server : Server::http(addr)?,
+                engine,
+                submissions : Mutex::new(Submissions::default()),
+            }
+        )
+    }
+
+    /// the address the server is listening on, useful when bound to port 0
+    pub fn local_addr(&self) -> crate::Result<SocketAddr> {
+        Ok(self.server.server_addr().to_ip().ok_or("Server is not listening on an IP address")?)
+    }
+
+    /// serves requests one after the other until the server is shut down
+    pub fn run(&self) -> crate::Result<()> {
+        for request in self.server.incoming_requests() {
+            if let Err(e) = self.handle(request) {
+                eprintln!("Error in serving request, {}", e);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle(&self, mut request : Request) -> crate::Result<()> {
+        let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+        let (path, query) = (path.to_string(), query.to_string());
+        let segments : Vec<&str> = path.trim_matches('/').split('/').collect();
+
+        let (status, body) = match (request.method(), segments.as_slice()) {
+            (Method::Post, ["transactions"]) => {
+                let key = request.headers().iter()
+                    .find(|header| header.field.equiv("Idempotency-Key"))
+                    .map(|header| header.value.to_string());
+                let mut content = String::new();
+                request.as_reader().read_to_string(&mut content)?;
+                self.submit(&content, key)?
+            },
+            (Method::Get, ["clients"]) => self.clients()?,
+            (Method::Get, ["clients", id]) => self.client(id, &query)?,
+            (Method::Get, ["transactions", tx]) => self.transaction(tx)?,
+            (Method::Get, ["openapi.json"]) => (200, OPENAPI.to_string()),
+            (_, ["transactions"] | ["clients"] | ["clients", _] | ["transactions", _] | ["openapi.json"]) => {
+                error(405, "Method not allowed")?
+            },
+            _ => error(404, "Not found")?,
+        };
+
+        let content_type = Header::from_bytes("Content-Type", "application/json")
+            .map_err(|_| "Invalid header")?;
+        let response = Response::from_string(body)
+            .with_status_code(status)
+            .with_header(content_type);
+
+        request.respond(response)?;
+        Ok(())
+    }
+
+    /// `POST /transactions`, at most once for each idempotency key
+    fn submit(&self, content : &str, key : Option<String>) -> crate::Result<(u16, String)> {
+        let transaction : Transaction = match serde_json::from_str(content) {
+            Ok(transaction) => transaction,
+            Err(e) => return error(400, &e.to_string()),
+        };
+
+        let mut submissions = self.submissions.lock().map_err(|_| "Submissions are unavailable")?;
+        if let Some(submission) = key.as_ref().and_then(|key| submissions.by_key.get(key)) {
+            if submission.transaction != transaction {
+                return error(409, "A different transaction was already submitted with this Idempotency-Key");
+            }
+            return Ok((201, submission.body.clone()));
+        }
+
+        // an applied transaction replies with the resulting balance of the client
+        let (status, body) = {
+            let mut engine = self.engine.lock().map_err(|_| "Engine is unavailable")?;
+            let (client, tx, currency) = (transaction.client, transaction.tx, transaction.currency);
+
+            if key.is_none() {
+                if let Some(same) = applied_before(&engine, &transaction) {
+                    if !same {
+                        return error(409, &format!("A different transaction was already applied with tx {}", tx));
+                    }
+                    let ledger = engine.get_ledger(client).ok_or(LedgerError::CustomerMissing(client))?;
+                    let balance = ledger.balance_in(currency).unwrap_or(ledger.get_balance());
+                    return Ok((200, serde_json::to_string(balance)?));
+                }
+            }
+
+            match engine.apply(transaction.clone()) {
+                Ok(_) => {
+                    // a dispute is in the currency of the transaction it refers to
+                    let ledger = engine.get_ledger(client).ok_or(LedgerError::CustomerMissing(client))?;
+                    let currency = ledger.get_record(tx).map(|record| record.currency).unwrap_or(currency);
+                    let balance = ledger.balance_in(currency).unwrap_or(ledger.get_balance());
+                    (201, serde_json::to_string(balance)?)
+                },
+                Err(e) => error(status_of(&e), &e.to_string())?,
+            }
+        };
+
+        if let (201, Some(key)) = (status, key) {
+            submissions.insert(key, Submission { transaction, body : body.clone() });
+        }
+        Ok((status, body))
+    }
+
+    /// `GET /clients`
+    fn clients(&self) -> crate::Result<(u16, String)> {
+        let engine = self.engine.lock().map_err(|_| "Engine is unavailable")?;
+        let balances : Vec<_> = engine.iter().collect();
+        Ok((200, serde_json::to_string(&balances)?))
+    }
+
+    /// `GET /clients/{id}`, in the default currency unless the query gives one
+    fn client(&self, id : &str, query : &str) -> crate::Result<(u16, String)> {
+        let Ok(client) = id.parse::<ClientId>() else {
+            return error(400, &format!("Invalid client id {}", id));
+        };
+
+        let currency = query.split('&')
+            .find_map(|param| param.strip_prefix("currency="))
+            .map(str::parse::<Currency>)
+            .transpose();
+        let currency = match currency {
+            Ok(currency) => currency.unwrap_or_default(),
+            Err(e) => return error(400, &e),
+        };
+
+        let engine = self.engine.lock().map_err(|_| "Engine is unavailable")?;
+        let Some(ledger) = engine.get_ledger(client) else {
+            return error(404, &LedgerError::CustomerMissing(client).to_string());
+        };
+
+        match ledger.balance_in(currency) {
+            Some(balance) => Ok((200, serde_json::to_string(balance)?)),
+            None => error(404, &format!("Client {} has no {} balance", client, currency)),
+        }
+    }
+
+    /// `GET /transactions/{tx}`
+    fn transaction(&self, tx : &str) -> crate::Result<(u16, String)> {
+        let Ok(tx) = tx.parse::<TransactionId>() else {
+            return error(400, &format!("Invalid transaction id {}", tx));
+        };
+
+        let engine = self.engine.lock().map_err(|_| "Engine is unavailable")?;
+        match engine.find_transaction(tx) {
+            Some((client, amount)) => Ok((200, serde_json::to_string(&TransactionBody { tx, client, amount })?)),
+            None => error(404, &format!("Transaction {} not found", tx)),
+        }
+    }
+}
+
+/// `status_of` maps an error from the engine to the http status code
+/// whether a deposit or withdrawal with the tx id of the transaction was
+/// applied before, and if so whether it was the same one. None for other
+/// types of transactions
+fn applied_before(engine : &TransactionEngine, transaction : &Transaction) -> Option<bool> {
+    let (kind, amount) = match transaction.txn_type {
+        TransactionType::Deposit { amount } => (RecordKind::Deposit, amount),
+        TransactionType::Withdrawal { amount } => (RecordKind::Withdrawal, amount),
+        _ => return None,
+    };
+
+    let (client, _) = engine.find_transaction(transaction.tx)?;
+    let record = engine.get_ledger(client)?.get_record(transaction.tx)?;
+    Some(client == transaction.client && record.kind == kind && record.amount == amount && record.currency == transaction.currency)
+}
+
+fn status_of(e : &crate::Error) -> u16 {
+    match e.downcast_ref::<LedgerError>() {
+        Some(LedgerError::CustomerMissing(_)) => 404,
+        Some(LedgerError::AccountLocked) => 423,
+        Some(LedgerError::InsufficentFunds { .. }) => 422,
+        Some(LedgerError::NotPermitted(_)) => 403,
+        Some(LedgerError::LimitExceeded { .. }) => 422,
+        Some(LedgerError::Rejected(_)) => 403,
+        None => 422,
+    }
+}
+
+fn error(status : u16, message : &str) -> crate::Result<(u16, String)> {
+    Ok((status, serde_json::to_string(&ErrorBody { error : message })?))
+}
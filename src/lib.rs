@@ -1,4 +1,13 @@
 pub mod transaction;
+pub mod readers;
+pub mod server;
+pub mod notify;
+pub mod balances;
+pub mod validation;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "async")]
+pub mod stream;
 
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
 pub type Result<T> = std::result::Result<T, Error>;
@@ -1,60 +1,785 @@
 use std::{env};
 use std::io;
+use std::sync::{Arc, Mutex};
 
-mod readers;
+use serde::Serialize;
 
-use txnengine::transaction::{Transaction, TransactionEngine};
-use readers::{CsvFileReader};
+use txnengine::transaction::{ClientId, Origin, TransactionEngine, TransactionId};
+use txnengine::transaction::amount::Amount;
+use txnengine::transaction::currency::Currency;
+use txnengine::transaction::config::{BalancePolicy, EngineConfig, LockPolicy, WithdrawalDisputes};
+use txnengine::transaction::fees::FeeSchedule;
+use txnengine::transaction::interest::InterestSchedule;
+use txnengine::transaction::ledger::WithoutCurrency;
+use txnengine::transaction::limits::WithdrawalLimits;
+use txnengine::transaction::rates::RateTable;
+use txnengine::transaction::observer::ObserverFactory;
+use txnengine::transaction::screening::{Action, Rules, ScreeningFactory};
+use txnengine::transaction::sharded::ShardedEngine;
+use txnengine::readers::{self, InputOrder, MultiFileReader, SourcedTransaction};
+use txnengine::server::TcpServer;
+use txnengine::notify::{FileSink, Notifier, WebhookSink};
+use txnengine::balances::{self, Delta, Difference, Summary};
+use txnengine::validation::Validation;
 
-/// `process_reader` takes an iterator over Transaction. It does not
-/// matter where the transactions are coming from.assert_eq!
-/// 
+/// `process_reader` takes an iterator over transactions. It does not
+/// matter where the transactions are coming from.
+///
 /// Returns the TransactionEngine that holds the ending balances
 /// of all customers after processing the iterator
-fn process_reader<T>(transcactions : T) -> TransactionEngine
+fn process_reader<T>(transcactions : T, config : EngineConfig, screening : Option<ScreeningFactory>,
+    observers : &[ObserverFactory]) -> TransactionEngine
     where
-        T : Iterator<Item = Transaction> 
+        T : Iterator<Item = SourcedTransaction>
 {
-    let mut engine = TransactionEngine::new();
+    let mut engine = TransactionEngine::with_config(config);
+    if let Some(screening) = screening {
+        engine = engine.with_screening(screening.create());
+    }
+    for observer in observers {
+        engine = engine.with_observer(observer.create());
+    }
 
     for t in transcactions {
-        if let Err(e) = engine.apply(t) {
-            eprintln!("Error in applying transaction, {}", e);
+        if let Err(e) = engine.apply(t.transaction) {
+            eprintln!("Error in applying transaction from {}, {}", t.provenance, e);
         }
     }
 
     engine
 }
 
-fn filename_from_args() -> txnengine::Result<String> {
-    if let Some(file_name) = env::args().nth(1) {
-        return Ok(file_name);
+/// `process_sharded` is same as `process_reader` but spreads the clients
+/// over a number of worker threads. The observers are also given to the
+/// combined engine, for the operator transactions applied afterwards
+fn process_sharded<T>(transcactions : T, shards : usize, config : EngineConfig, screening : Option<ScreeningFactory>,
+    observers : &[ObserverFactory]) -> txnengine::Result<TransactionEngine>
+    where
+        T : Iterator<Item = SourcedTransaction>
+{
+    let mut engine = ShardedEngine::new(shards).with_config(config);
+    if let Some(screening) = screening {
+        engine = engine.with_screening(screening);
+    }
+    for observer in observers {
+        engine = engine.with_observer(observer.clone());
+    }
+
+    let mut combined = engine.process(
+        transcactions.map(|t| (t.provenance, t.transaction)),
+        |provenance, e| eprintln!("Error in applying transaction from {}, {}", provenance, e))?;
+    for observer in observers {
+        combined = combined.with_observer(observer.create());
+    }
+    Ok(combined)
+}
+
+/// `Options` are the command line arguments given to the program
+struct Options {
+    order : InputOrder,
+    shards : Option<usize>,
+    fees : Option<String>,
+    fee_report : Option<String>,
+    ops : Option<String>,
+    allow_locked : Option<String>,
+    withdrawal_disputes : Option<String>,
+    balance_policy : Option<String>,
+    overdraft : Option<String>,
+    rates : Option<String>,
+    interest : Option<String>,
+    interest_period : Option<u64>,
+    withdrawal_limits : Option<String>,
+    screening : Option<String>,
+    flagged_report : Option<String>,
+    notify : Vec<String>,
+    dead_letter : Option<String>,
+    inputs : Vec<String>,
+}
+
+/// Usage: txnengine [--merge] [--shards N] [--fees schedule.csv]
+///     [--fee-report fees.csv] [--ops ops.csv] [--allow-locked types]
+///     [--withdrawal-disputes deposit|provisional|reject]
+///     [--balance-policy strict|disputes] [--overdraft limits.csv]
+///     [--rates rates.csv] [--interest rates.csv] [--interest-period days]
+///     [--withdrawal-limits limits.csv] [--screening flag|reject]
+///     [--flagged-report flagged.csv] [--notify url|file]... [--dead-letter file]
+///     <file|directory|glob>...
+///
+/// Files are processed one after the other unless `--merge` is given, in
+/// which case they are merged by timestamp. `--shards` applies the
+/// transactions on N worker threads. `--fees` loads the fee schedule and
+/// `--fee-report` writes all fees charged to the given file. `--ops` applies
+/// the operator transactions of the given file after all the inputs.
+/// `--allow-locked` is a comma separated list of transaction types that are
+/// still applied on a locked account and `--withdrawal-disputes` is how a
+/// disputed withdrawal moves funds. `--balance-policy` decides what can take
+/// the available funds below zero, while `--overdraft` lets the clients of
+/// the given file go below zero up to their limit. `--rates` loads the
+/// exchange rates used by conversions. `--interest` loads the yearly interest
+/// rates earned on available funds, which is posted every `--interest-period`
+/// days. `--withdrawal-limits` loads the withdrawal limits of clients.
+/// `--screening` runs the built-in screening rules, which flag or reject what
+/// they catch, and `--flagged-report` writes the flagged transactions.
+/// `--notify` sends account lock events to an `http://` webhook or appends
+/// them to a file, and can be given more than once. Webhook events that
+/// cannot be delivered go to the `--dead-letter` file
+fn options_from_args(mut args : impl Iterator<Item = String>) -> txnengine::Result<Options> {
+    let mut options = Options {
+        order : InputOrder::Sequential,
+        shards : None,
+        fees : None,
+        fee_report : None,
+        ops : None,
+        allow_locked : None,
+        withdrawal_disputes : None,
+        balance_policy : None,
+        overdraft : None,
+        rates : None,
+        interest : None,
+        interest_period : None,
+        withdrawal_limits : None,
+        screening : None,
+        flagged_report : None,
+        notify : Vec::new(),
+        dead_letter : None,
+        inputs : Vec::new(),
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--merge" => options.order = InputOrder::Merged,
+            "--sequential" => options.order = InputOrder::Sequential,
+            "--shards" => {
+                let shards = args.next().ok_or("Missing number of shards")?;
+                options.shards = Some(shards.parse()?);
+            },
+            "--fees" => {
+                options.fees = Some(args.next().ok_or("Missing fee schedule file")?);
+            },
+            "--fee-report" => {
+                options.fee_report = Some(args.next().ok_or("Missing fee report file")?);
+            },
+            "--ops" => {
+                options.ops = Some(args.next().ok_or("Missing operator file")?);
+            },
+            "--allow-locked" => {
+                options.allow_locked = Some(args.next().ok_or("Missing transaction types allowed on locked accounts")?);
+            },
+            "--withdrawal-disputes" => {
+                options.withdrawal_disputes = Some(args.next().ok_or("Missing withdrawal dispute semantics")?);
+            },
+            "--balance-policy" => {
+                options.balance_policy = Some(args.next().ok_or("Missing balance policy")?);
+            },
+            "--overdraft" => {
+                options.overdraft = Some(args.next().ok_or("Missing overdraft limits file")?);
+            },
+            "--rates" => {
+                options.rates = Some(args.next().ok_or("Missing exchange rates file")?);
+            },
+            "--interest" => {
+                options.interest = Some(args.next().ok_or("Missing interest rates file")?);
+            },
+            "--interest-period" => {
+                let days = args.next().ok_or("Missing interest period")?;
+                options.interest_period = Some(days.parse()?);
+            },
+            "--withdrawal-limits" => {
+                options.withdrawal_limits = Some(args.next().ok_or("Missing withdrawal limits file")?);
+            },
+            "--screening" => {
+                options.screening = Some(args.next().ok_or("Missing screening action")?);
+            },
+            "--flagged-report" => {
+                options.flagged_report = Some(args.next().ok_or("Missing flagged report file")?);
+            },
+            "--notify" => {
+                options.notify.push(args.next().ok_or("Missing notification url or file")?);
+            },
+            "--dead-letter" => {
+                options.dead_letter = Some(args.next().ok_or("Missing dead letter file")?);
+            },
+            flag if flag.starts_with("--") => {
+                return Err(format!("Unknown option {}", flag).into());
+            },
+            _ => options.inputs.push(arg),
+        }
+    }
+
+    if options.inputs.is_empty() {
+        return Err("Missing file name to process".into());
+    }
+
+    Ok(options)
+}
+
+/// `notifiers` creates a notifier for the webhooks, and one for each file,
+/// given with `--notify`
+fn notifiers(options : &Options) -> txnengine::Result<Vec<ObserverFactory>> {
+    let (urls, files) : (Vec<String>, Vec<String>) = options.notify.iter()
+        .cloned()
+        .partition(|target| target.starts_with("http://"));
+
+    let mut observers = Vec::new();
+    if !urls.is_empty() {
+        let mut webhook = WebhookSink::new(urls)?;
+        if let Some(dead_letter) = &options.dead_letter {
+            webhook = webhook.with_dead_letter(dead_letter);
+        }
+        observers.push(ObserverFactory::new(move || Box::new(Notifier::new(Box::new(webhook.clone())))));
+    }
+    else if options.dead_letter.is_some() {
+        return Err("--dead-letter needs a webhook given with --notify".into());
     }
 
-    return Err("Missing file name to process".into());
+    for file in files {
+        observers.push(ObserverFactory::new(move || Box::new(Notifier::new(Box::new(FileSink::new(&file))))));
+    }
+    Ok(observers)
 }
 
-/// `write_balances` iterates over all custmers and serializes the 
-///  output to the standard output
+/// `write_balances` iterates over all custmers and serializes the
+///  output to the standard output. The currency column is only written
+///  when there are balances in other currencies than the default one
 fn write_balances(engine : &TransactionEngine) -> txnengine::Result<()> {
     let mut writer = csv::Writer::from_writer(io::stdout());
-    
+    let with_currency = engine.iter().any(|balance| !balance.currency().is_default());
+
     for balance in engine.iter() {
-        writer.serialize(balance)?;
+        if with_currency {
+            writer.serialize(balance)?;
+        }
+        else {
+            writer.serialize(WithoutCurrency(balance))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// `apply_operator_file` applies the transactions of an operator file onto
+/// the engine. Unlike the inputs, these can lock, unlock and adjust accounts
+fn apply_operator_file(engine : &mut TransactionEngine, path : &str) -> txnengine::Result<()> {
+    let reader = MultiFileReader::new(&[path.into()])?;
+
+    for t in reader.iter(InputOrder::Sequential)? {
+        if let Err(e) = engine.apply(t.transaction.with_origin(Origin::Operator)) {
+            eprintln!("Error in applying operator transaction from {}, {}", t.provenance, e);
+        }
+    }
+
+    Ok(())
+}
+
+/// `FeeReportRecord` is a line of the fee report
+#[derive(Serialize)]
+struct FeeReportRecord {
+    client : ClientId,
+    tx : TransactionId,
+    source : &'static str,
+    amount : Amount,
+    currency : Currency,
+}
+
+/// `write_fee_report` writes every fee charged to each client
+fn write_fee_report(engine : &TransactionEngine, path : &str) -> txnengine::Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+
+    for ledger in engine.ledgers() {
+        for fee in ledger.fees() {
+            writer.serialize(FeeReportRecord {
+                client : ledger.get_balance().client(),
+                tx : fee.tx,
+                source : fee.source.name(),
+                amount : fee.amount,
+                currency : fee.currency,
+            })?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// `FlaggedReportRecord` is a line of the flagged report
+#[derive(Serialize)]
+struct FlaggedReportRecord<'a> {
+    client : ClientId,
+    tx : TransactionId,
+    #[serde(rename = "type")]
+    txn_type : &'static str,
+    reason : &'a str,
+}
+
+/// `write_flagged_report` writes every transaction flagged by the screening
+fn write_flagged_report(engine : &TransactionEngine, path : &str) -> txnengine::Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+
+    for flagged in engine.flagged() {
+        writer.serialize(FlaggedReportRecord {
+            client : flagged.client,
+            tx : flagged.tx,
+            txn_type : flagged.txn_type,
+            reason : &flagged.reason,
+        })?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// `ViolationRecord` is a line of the verify report
+#[derive(Serialize)]
+struct ViolationRecord {
+    client : ClientId,
+    currency : Currency,
+    invariant : &'static str,
+    expected : Amount,
+    actual : Amount,
+}
+
+/// `verify` processes the inputs and, instead of the balances, writes the
+/// violations of the ledger invariants to the standard output. It fails if
+/// there are any. The `--notify` targets are not sent anything
+///
+/// Usage: txnengine verify [options] <file|directory|glob>...
+fn verify() -> txnengine::Result<()> {
+    let options = options_from_args(env::args().skip(2))?;
+    let engine = process_with(&options, &[])?;
+
+    let violations = engine.verify();
+    let mut writer = csv::Writer::from_writer(io::stdout());
+    for violation in &violations {
+        writer.serialize(ViolationRecord {
+            client : violation.client,
+            currency : violation.currency,
+            invariant : violation.invariant.name(),
+            expected : violation.expected,
+            actual : violation.actual,
+        })?;
+    }
+    writer.flush()?;
+
+    if !violations.is_empty() {
+        return Err(format!("{} invariant violations found", violations.len()).into());
+    }
+    Ok(())
+}
+
+/// `DifferenceRecord` is a line of the reconcile report
+#[derive(Serialize)]
+struct DifferenceRecord {
+    client : ClientId,
+    currency : Currency,
+    difference : &'static str,
+    field : &'static str,
+    expected : String,
+    actual : String,
+}
+
+impl From<&Difference> for DifferenceRecord {
+    fn from(difference : &Difference) -> Self {
+        let (client, currency) = difference.key();
+        let (kind, field, expected, actual) = match difference {
+            Difference::Missing(_) => ("missing", "", String::new(), String::new()),
+            Difference::Extra(_) => ("extra", "", String::new(), String::new()),
+            Difference::Mismatch { field, expected, actual } => ("mismatch", field.name(), field.value(expected), field.value(actual)),
+        };
+        DifferenceRecord { client, currency, difference : kind, field, expected, actual }
+    }
+}
+
+/// `reconcile` compares the balances of the engine, or of a balances file
+/// given with `--snapshot`, against the expected balances file and writes
+/// the differences to the standard output. It fails if there are any. The
+/// `--notify` targets are not sent anything
+///
+/// Usage: txnengine reconcile <expected.csv> [--tolerance amount]
+///     [--snapshot balances.csv | [options] <file|directory|glob>...]
+fn reconcile() -> txnengine::Result<()> {
+    let mut args = env::args().skip(2);
+    let expected = args.next().ok_or("Missing expected balances file")?;
+
+    let mut tolerance = 0.0;
+    let mut snapshot = None;
+    let mut rest = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--tolerance" => tolerance = args.next().ok_or("Missing tolerance")?.parse()?,
+            "--snapshot" => snapshot = Some(args.next().ok_or("Missing snapshot file")?),
+            _ => rest.push(arg),
+        }
+    }
+
+    let actual = match snapshot {
+        Some(_) if !rest.is_empty() => return Err("--snapshot cannot be used with inputs".into()),
+        Some(snapshot) => balances::from_csv(&snapshot)?,
+        None => balances::from_engine(&process_with(&options_from_args(rest.into_iter())?, &[])?),
+    };
+    let differences = balances::reconcile(&balances::from_csv(&expected)?, &actual, tolerance);
+
+    let mut writer = csv::Writer::from_writer(io::stdout());
+    for difference in &differences {
+        writer.serialize(DifferenceRecord::from(difference))?;
+    }
+    writer.flush()?;
+
+    if !differences.is_empty() {
+        return Err(format!("{} differences found", differences.len()).into());
+    }
+    Ok(())
+}
+
+/// `DeltaRecord` is a line of the diff report, with the amounts written in
+/// the minor units of the currency
+#[derive(Serialize)]
+struct DeltaRecord {
+    client : ClientId,
+    currency : Currency,
+    change : &'static str,
+    available : String,
+    held : String,
+    total : String,
+    was_locked : bool,
+    locked : bool,
+}
+
+impl From<&Delta> for DeltaRecord {
+    fn from(delta : &Delta) -> Self {
+        DeltaRecord {
+            client : delta.client,
+            currency : delta.currency,
+            change : delta.change.name(),
+            available : delta.available.display_in(delta.currency),
+            held : delta.held.display_in(delta.currency),
+            total : delta.total.display_in(delta.currency),
+            was_locked : delta.was_locked,
+            locked : delta.locked,
+        }
+    }
+}
+
+/// `NetRecord` is the net movement of a currency in the diff summary
+#[derive(Serialize)]
+struct NetRecord {
+    currency : Currency,
+    available : String,
+    held : String,
+    total : String,
+}
+
+/// `SummaryRecord` is the summary of the diff report
+#[derive(Serialize)]
+struct SummaryRecord {
+    compared : usize,
+    unchanged : usize,
+    changed : usize,
+    added : usize,
+    removed : usize,
+    locked : usize,
+    unlocked : usize,
+    net : Vec<NetRecord>,
+}
+
+impl From<&Summary> for SummaryRecord {
+    fn from(summary : &Summary) -> Self {
+        let net = summary.net.iter()
+            .map(|(currency, net)| NetRecord {
+                currency : *currency,
+                available : net.available.display_in(*currency),
+                held : net.held.display_in(*currency),
+                total : net.total.display_in(*currency),
+            })
+            .collect();
+
+        SummaryRecord {
+            compared : summary.compared,
+            unchanged : summary.unchanged,
+            changed : summary.changed,
+            added : summary.added,
+            removed : summary.removed,
+            locked : summary.locked,
+            unlocked : summary.unlocked,
+            net,
+        }
+    }
+}
+
+/// `DiffReport` is the diff report written with `--json`
+#[derive(Serialize)]
+struct DiffReport {
+    deltas : Vec<DeltaRecord>,
+    summary : SummaryRecord,
+}
+
+/// `diff` compares two balances files, e.g. the output of two runs with a
+/// different policy, and writes how much each balance moved to the standard
+/// output. The deltas are written as csv with the summary on the standard
+/// error, or both as a single json document with `--json`
+///
+/// Usage: txnengine diff <before.csv> <after.csv> [--tolerance amount] [--json]
+fn diff() -> txnengine::Result<()> {
+    let mut files = Vec::new();
+    let mut tolerance = 0.0;
+    let mut json = false;
+
+    let mut args = env::args().skip(2);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--tolerance" => tolerance = args.next().ok_or("Missing tolerance")?.parse()?,
+            "--json" => json = true,
+            flag if flag.starts_with("--") => return Err(format!("Unknown option {}", flag).into()),
+            _ => files.push(arg),
+        }
+    }
+    let [before, after] = files.as_slice() else {
+        return Err("Expected the two balances files to compare".into());
+    };
+
+    let (before, after) = (balances::from_csv(before)?, balances::from_csv(after)?);
+    let deltas = balances::diff(&before, &after, tolerance);
+    let summary = Summary::new(&before, &after, &deltas);
+
+    if json {
+        let report = DiffReport {
+            deltas : deltas.iter().map(DeltaRecord::from).collect(),
+            summary : SummaryRecord::from(&summary),
+        };
+        serde_json::to_writer_pretty(io::stdout(), &report)?;
+        println!();
+        return Ok(());
+    }
+
+    let mut writer = csv::Writer::from_writer(io::stdout());
+    for delta in &deltas {
+        writer.serialize(DeltaRecord::from(delta))?;
     }
+    writer.flush()?;
 
+    eprintln!("{} balances compared, {} unchanged, {} changed, {} added, {} removed, {} locked, {} unlocked",
+        summary.compared, summary.unchanged, summary.changed, summary.added, summary.removed, summary.locked, summary.unlocked);
+    for (currency, net) in &summary.net {
+        let name = if currency.is_default() { "default" } else { currency.code() };
+        eprintln!("Net {}: available {}, held {}, total {}", name,
+            net.available.display_in(*currency), net.held.display_in(*currency), net.total.display_in(*currency));
+    }
     Ok(())
 }
 
-/// The filename to process is passed as an argument.
-/// 
-/// It uses the CsvReader to read get an iterator over Transaction,
+/// `OutcomeRecord` is a line of the validate report
+#[derive(Serialize)]
+struct OutcomeRecord {
+    outcome : &'static str,
+    kind : &'static str,
+    count : usize,
+}
+
+/// `validate` applies the inputs to a throwaway engine and writes how many
+/// rows were applied, rejected by each kind of error or could not be parsed
+/// to the standard output, instead of the balances. With `--max-reject-rate`
+/// it fails when more than the given percentage of rows were not applied.
+///
+/// The inputs are applied on a single thread without the operator file, and
+/// nothing else is written or sent
+///
+/// Usage: txnengine validate [--max-reject-rate percent] [options] <file|directory|glob>...
+fn validate() -> txnengine::Result<()> {
+    let mut max_reject_rate = None;
+    let mut rest = Vec::new();
+
+    let mut args = env::args().skip(2);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--max-reject-rate" => {
+                let rate : f64 = args.next().ok_or("Missing maximum reject rate")?.parse()?;
+                max_reject_rate = Some(rate);
+            },
+            _ => rest.push(arg),
+        }
+    }
+
+    let options = options_from_args(rest.into_iter())?;
+    let reader = MultiFileReader::new(&readers::expand_inputs(&options.inputs)?)?;
+    let mut engine = TransactionEngine::with_config(engine_config(&options)?);
+    if let Some(screening) = screening(&options)? {
+        engine = engine.with_screening(screening.create());
+    }
+
+    let validation = Validation::run(&mut engine, reader, options.order,
+        |provenance, e| eprintln!("Error in applying transaction from {}, {}", provenance, e))?;
+
+    let mut writer = csv::Writer::from_writer(io::stdout());
+    writer.serialize(OutcomeRecord { outcome : "applied", kind : "", count : validation.applied })?;
+    for (kind, count) in &validation.rejected {
+        writer.serialize(OutcomeRecord { outcome : "rejected", kind, count : *count })?;
+    }
+    writer.serialize(OutcomeRecord { outcome : "unparsed", kind : "", count : validation.unparsed })?;
+    writer.flush()?;
+
+    eprintln!("{} rows, {} applied, {} failed ({:.2}%)", validation.rows(), validation.applied, validation.failed(),
+        validation.reject_rate());
+    match max_reject_rate {
+        Some(max) if validation.reject_rate() > max => {
+            Err(format!("Reject rate {:.2}% is over {}%", validation.reject_rate(), max).into())
+        },
+        _ => Ok(()),
+    }
+}
+
+/// `serve` runs the engine as a TCP server, see `txnengine::server`. The
+/// engine is configured by the same options as a normal run
+///
+/// Usage: txnengine serve <address> [options]
+fn serve() -> txnengine::Result<()> {
+    let (addr, engine) = served_engine()?;
+
+    let engine = Arc::new(Mutex::new(engine));
+    let server = TcpServer::bind(addr.as_str(), engine)?;
+    eprintln!("Listening on {}", server.local_addr()?);
+
+    server.run()
+}
+
+/// `serve_http` runs the engine behind the REST API, see `txnengine::http`.
+/// The engine is configured by the same options as a normal run
+///
+/// Usage: txnengine http <address> [options]
+#[cfg(feature = "http")]
+fn serve_http() -> txnengine::Result<()> {
+    let (addr, engine) = served_engine()?;
+
+    let engine = Arc::new(Mutex::new(engine));
+    let server = txnengine::http::HttpServer::bind(&addr, engine)?;
+    eprintln!("Listening on http://{}", server.local_addr()?);
+
+    server.run()
+}
+
+/// `served_engine` reads the address to listen on and the options of the
+/// server commands, and creates the engine with the config and screening
+/// they give
+fn served_engine() -> txnengine::Result<(String, TransactionEngine)> {
+    let mut args = env::args().skip(2);
+    let addr = args.next().ok_or("Missing address to listen on")?;
+
+    let options = options_from_args(args)?;
+    if !options.inputs.is_empty() {
+        return Err("A server does not take inputs".into());
+    }
+
+    let mut engine = TransactionEngine::with_config(engine_config(&options)?);
+    if let Some(screening) = screening(&options)? {
+        engine = engine.with_screening(screening.create());
+    }
+
+    Ok((addr, engine))
+}
+
+/// The files to process are passed as arguments.
+///
+/// It uses the MultiFileReader to get an iterator over Transaction,
 /// and applies each transaction onto the TransactionEngine
-/// 
+///
 fn main() -> txnengine::Result<()> {
-    let mut reader = CsvFileReader::new(&filename_from_args()?)?;
-    let engine = process_reader(reader.iter());
+    match env::args().nth(1).as_deref() {
+        Some("serve") => return serve(),
+        #[cfg(feature = "http")]
+        Some("http") => return serve_http(),
+        Some("verify") => return verify(),
+        Some("reconcile") => return reconcile(),
+        Some("diff") => return diff(),
+        Some("validate") => return validate(),
+        _ => {},
+    }
+
+    let options = options_from_args(env::args().skip(1))?;
+    let engine = process(&options)?;
     write_balances(&engine)?;
 
+    if let Some(fee_report) = &options.fee_report {
+        write_fee_report(&engine, fee_report)?;
+    }
+    if let Some(flagged_report) = &options.flagged_report {
+        write_flagged_report(&engine, flagged_report)?;
+    }
+
     Ok(())
 }
+
+/// `process` applies the inputs, and then the operator file, with the
+/// config given by the options
+fn process(options : &Options) -> txnengine::Result<TransactionEngine> {
+    process_with(options, &notifiers(options)?)
+}
+
+/// `process_with` is `process` with the given observers instead of the
+/// notifiers of the options. The read-only commands pass none, so that
+/// nothing is sent for a run that only reports
+fn process_with(options : &Options, observers : &[ObserverFactory]) -> txnengine::Result<TransactionEngine> {
+    let files = readers::expand_inputs(&options.inputs)?;
+
+    let reader = MultiFileReader::new(&files)?;
+    let config = engine_config(options)?;
+    let screening = screening(options)?;
+
+    let transactions = reader.iter(options.order)?;
+    let mut engine = match options.shards {
+        Some(shards) => process_sharded(transactions, shards, config, screening, observers)?,
+        None => process_reader(transactions, config, screening, observers),
+    };
+
+    if let Some(ops) = &options.ops {
+        apply_operator_file(&mut engine, ops)?;
+    }
+
+    Ok(engine)
+}
+
+/// `engine_config` loads the config of the engine given by the options
+fn engine_config(options : &Options) -> txnengine::Result<EngineConfig> {
+    let mut config = EngineConfig::default();
+    if let Some(fees) = &options.fees {
+        config = config.with_fees(FeeSchedule::from_csv(fees)?);
+    }
+    if let Some(allowed) = &options.allow_locked {
+        config = config.with_lock_policy(LockPolicy::parse(allowed)?);
+    }
+    if let Some(semantics) = &options.withdrawal_disputes {
+        config = config.with_withdrawal_disputes(WithdrawalDisputes::parse(semantics)?);
+    }
+    match (&options.balance_policy, &options.overdraft) {
+        (Some(_), Some(_)) => return Err("--balance-policy and --overdraft cannot be used together".into()),
+        (Some(policy), None) => config = config.with_balance_policy(BalancePolicy::parse(policy)?),
+        (None, Some(limits)) => config = config.with_balance_policy(BalancePolicy::overdraft_from_csv(limits)?),
+        (None, None) => {},
+    }
+    if let Some(rates) = &options.rates {
+        config = config.with_rates(RateTable::from_csv(rates)?);
+    }
+    match (&options.interest, options.interest_period) {
+        (Some(rates), period) => {
+            let mut interest = InterestSchedule::from_csv(rates)?;
+            if let Some(days) = period {
+                interest = interest.with_period_days(days)?;
+            }
+            config = config.with_interest(interest);
+        },
+        (None, Some(_)) => return Err("--interest-period needs --interest".into()),
+        (None, None) => {},
+    }
+    if let Some(limits) = &options.withdrawal_limits {
+        config = config.with_withdrawal_limits(WithdrawalLimits::from_csv(limits)?);
+    }
+
+    Ok(config)
+}
+
+/// `screening` creates the built-in screening rules, if asked for
+fn screening(options : &Options) -> txnengine::Result<Option<ScreeningFactory>> {
+    let Some(action) = &options.screening else {
+        return Ok(None);
+    };
+
+    let action = Action::parse(action)?;
+    Ok(Some(ScreeningFactory::new(move || Box::new(Rules::builtin(action)))))
+}
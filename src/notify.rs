@@ -0,0 +1,252 @@
+//! `txengine::notify::Notifier`
+//!
+//! An `EngineObserver` that sends a notification whenever a transaction
+//! locks a client's account, e.g. a chargeback. Each notification is a json
+//! object such as
+//!
+//! ```text
+//! {"event":"account_locked","tx":4,"type":"chargeback","client":1,"currency":"","available":"0.0000","held":"0.0000","total":"0.0000","locked":true}
+//! ```
+//!
+//! and is handed to a `Sink`:
+//!
+//! |Sink|Sends|
+//! |-|-|
+//! |`WebhookSink`|a POST to each url, retried with backoff, then to a dead letter file|
+//! |`FileSink`|a line appended to a file, for offline use|
+//! |`WriterSink`|a line to any writer, e.g. the standard output|
+//!
+//! Webhooks are plain `http://` urls and are posted from the thread applying
+//! the transaction, so a slow endpoint holds the engine back for as long as
+//! its retries take.
+use std::fs::OpenOptions;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::transaction::{Transaction, TransactionId};
+use crate::transaction::ledger::ClientBalance;
+use crate::transaction::observer::EngineObserver;
+
+/// how long a webhook has to accept the connection and reply
+const TIMEOUT : Duration = Duration::from_secs(5);
+
+/// `Sink` delivers a notification, given as json
+pub trait Sink : std::fmt::Debug + Send {
+    fn send(&mut self, payload : &str) -> crate::Result<()>;
+}
+
+/// `Notification` is the json sent for an event, the client is the one of
+/// the balance
+#[derive(Serialize)]
+struct Notification<'a> {
+    event : &'static str,
+    tx : TransactionId,
+    #[serde(rename = "type")]
+    txn_type : &'static str,
+    #[serde(flatten)]
+    balance : &'a ClientBalance,
+}
+
+/// `Notifier` sends account lock events to a sink
+#[derive(Debug)]
+pub struct Notifier {
+    sink : Box<dyn Sink>,
+}
+
+impl Notifier {
+    pub fn new(sink : Box<dyn Sink>) -> Self {
+        Notifier { sink }
+    }
+}
+
+impl EngineObserver for Notifier {
+    fn on_locked(&mut self, transaction : &Transaction, balance : &ClientBalance) {
+        let notification = Notification {
+            event : "account_locked",
+            tx : transaction.tx,
+            txn_type : transaction.txn_type.name(),
+            balance,
+        };
+
+        let result = serde_json::to_string(&notification)
+            .map_err(crate::Error::from)
+            .and_then(|payload| self.sink.send(&payload));
+        if let Err(e) = result {
+            eprintln!("Error in sending notification for transaction {}, {}", transaction.tx, e);
+        }
+    }
+}
+
+/// `WriterSink` writes each notification as a line
+#[derive(Debug)]
+pub struct WriterSink<W> {
+    writer : W,
+}
+
+impl WriterSink<io::Stdout> {
+    pub fn stdout() -> Self {
+        WriterSink { writer : io::stdout() }
+    }
+}
+
+impl<W : Write> WriterSink<W> {
+    pub fn new(writer : W) -> Self {
+        WriterSink { writer }
+    }
+}
+
+impl<W : Write + Send + std::fmt::Debug> Sink for WriterSink<W> {
+    fn send(&mut self, payload : &str) -> crate::Result<()> {
+        writeln!(self.writer, "{}", payload)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// `FileSink` appends each notification as a line to a file, which is
+/// created if needed. The file is opened for each line, so several sinks
+/// can share it
+#[derive(Debug, Clone)]
+pub struct FileSink {
+    path : PathBuf,
+}
+
+impl FileSink {
+    pub fn new(path : impl Into<PathBuf>) -> Self {
+        FileSink { path : path.into() }
+    }
+}
+
+impl Sink for FileSink {
+    fn send(&mut self, payload : &str) -> crate::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{}", payload)?;
+        Ok(())
+    }
+}
+
+/// a line of the dead letter file
+#[derive(Serialize)]
+struct DeadLetter<'a> {
+    url : &'a str,
+    error : String,
+    payload : &'a str,
+}
+
+/// `WebhookSink` posts each notification to every url. A failed post is
+/// retried after a backoff that doubles each time, and a notification that
+/// could still not be delivered is written to the dead letter file, if any
+#[derive(Debug, Clone)]
+pub struct WebhookSink {
+    urls : Vec<String>,
+    retries : u32,
+    backoff : Duration,
+    dead_letter : Option<PathBuf>,
+}
+
+impl WebhookSink {
+    /// posts to the given urls, with 3 retries starting 100ms apart
+    pub fn new(urls : Vec<String>) -> crate::Result<Self> {
+        for url in &urls {
+            parse_url(url)?;
+        }
+
+        Ok(WebhookSink {
+            urls,
+            retries : 3,
+            backoff : Duration::from_millis(100),
+            dead_letter : None,
+        })
+    }
+
+    pub fn with_retries(mut self, retries : u32, backoff : Duration) -> Self {
+        self.retries = retries;
+        self.backoff = backoff;
+        self
+    }
+
+    pub fn with_dead_letter(mut self, path : impl Into<PathBuf>) -> Self {
+        self.dead_letter = Some(path.into());
+        self
+    }
+
+    /// posts to a url, retrying until it succeeds or runs out of retries
+    fn deliver(&self, url : &str, payload : &str) -> crate::Result<()> {
+        let mut backoff = self.backoff;
+        let mut attempt = 0;
+        loop {
+            match post(url, payload) {
+                Ok(_) => return Ok(()),
+                Err(e) if attempt >= self.retries => return Err(e),
+                Err(_) => {
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                    attempt += 1;
+                },
+            }
+        }
+    }
+
+    fn write_dead_letter(&self, url : &str, error : &crate::Error, payload : &str) -> crate::Result<()> {
+        let Some(path) = &self.dead_letter else {
+            return Err(format!("Notification to {} failed, {}", url, error).into());
+        };
+
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        let line = serde_json::to_string(&DeadLetter { url, error : error.to_string(), payload })?;
+        writeln!(file, "{}", line)?;
+        Ok(())
+    }
+}
+
+impl Sink for WebhookSink {
+    fn send(&mut self, payload : &str) -> crate::Result<()> {
+        for url in &self.urls {
+            if let Err(e) = self.deliver(url, payload) {
+                self.write_dead_letter(url, &e, payload)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// splits an `http://host[:port][/path]` url into its address and path
+fn parse_url(url : &str) -> crate::Result<(String, &str)> {
+    let rest = url.strip_prefix("http://").ok_or(format!("Only http:// urls are supported, not {}", url))?;
+    let (host, path) = match rest.find('/') {
+        Some(at) => rest.split_at(at),
+        None => (rest, "/"),
+    };
+
+    if host.is_empty() {
+        return Err(format!("Missing host in url {}", url).into());
+    }
+    let addr = if host.contains(':') { host.to_string() } else { format!("{}:80", host) };
+    Ok((addr, path))
+}
+
+/// posts the json payload and fails unless the reply is a 2xx
+fn post(url : &str, payload : &str) -> crate::Result<()> {
+    let (addr, path) = parse_url(url)?;
+
+    let resolved = addr.to_socket_addrs()?.next().ok_or_else(|| format!("{} does not resolve to an address", addr))?;
+    let mut stream = TcpStream::connect_timeout(&resolved, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    write!(stream, "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path, addr, payload.len(), payload)?;
+
+    let mut status_line = String::new();
+    BufReader::new(stream).read_line(&mut status_line)?;
+    let status : u16 = status_line.split(' ').nth(1).ok_or("Missing status in reply")?.parse()?;
+
+    if !(200..300).contains(&status) {
+        return Err(format!("{} replied with {}", url, status).into());
+    }
+    Ok(())
+}
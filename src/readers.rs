@@ -1,21 +1,29 @@
-use csv::{Reader, DeserializeRecordsIter};
+use csv::{Reader, DeserializeRecordsIter, StringRecord};
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fmt;
 use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 
-use txnengine::transaction::{Transaction};
+use crate::transaction::{CsvTransaction, Transaction, Timestamp, TransactionId};
 
 /// `CsvFileReader` is used for reading from a csv based transaction
 /// file. The `iter` method returns an iterator that provides an iterator
 /// over all transactions found in the file
 pub struct CsvFileReader {
     reader : Reader<File>,
+    path : Arc<str>,
 }
 
 impl CsvFileReader {
-    pub fn new(path : &String) -> txnengine::Result<Self> {
+    pub fn new(path : &String) -> crate::Result<Self> {
         let rdr = csv::Reader::from_path(path)?;
         Ok(
             CsvFileReader {
                 reader : rdr,
+                path : Arc::from(path.as_str()),
            }
         )
     }
@@ -28,23 +36,41 @@ impl CsvFileReader {
             records : self.reader.deserialize(),
         }
     }
+
+    /// consumes the reader and returns an iterator that provides each
+    /// Transaction along with the file and line it was read from
+    ///
+    /// Unlike `iter`, a line that cannot be parsed is reported and skipped
+    /// instead of ending the iteration
+    pub fn into_sourced(mut self) -> crate::Result<SourcedCsvIterator> {
+        let headers = self.reader.headers()?.clone();
+        Ok(
+            SourcedCsvIterator {
+                reader : self.reader,
+                path : self.path,
+                headers,
+                record : StringRecord::new(),
+                parse_errors : ParseErrors::default(),
+            }
+        )
+    }
 }
 
 pub struct CsvFileIterator<'a> {
-    records: DeserializeRecordsIter<'a, File, Transaction>,
+    records: DeserializeRecordsIter<'a, File, CsvTransaction>,
 }
 
 impl<'a> Iterator for CsvFileIterator<'a> {
     type Item = Transaction;
 
     /// returns None when there are no more records in the file
-    /// 
+    ///
     /// In case of error, it prints to the error stream
     fn next(&mut self) -> Option<Self::Item> {
         let next_result = self.records.next()?;
 
         match next_result {
-            Ok(value) => Some(value),
+            Ok(CsvTransaction(value)) => Some(value),
             Err(e) => {
                 eprintln!("{}", e);
                 None
@@ -52,3 +78,264 @@ impl<'a> Iterator for CsvFileIterator<'a> {
         }
     }
 }
+
+/// `Provenance` records where a transaction came from, so that rejections
+/// can be traced back to the partner file that contained them
+#[derive(Debug, Clone)]
+pub struct Provenance {
+    pub source : Arc<str>,
+    pub line : u64,
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.source, self.line)
+    }
+}
+
+/// `SourcedTransaction` is a transaction along with its provenance
+#[derive(Debug)]
+pub struct SourcedTransaction {
+    pub provenance : Provenance,
+    pub transaction : Transaction,
+}
+
+/// `ParseErrors` counts the lines that could not be parsed and were
+/// skipped. Clones share the same count
+#[derive(Debug, Clone, Default)]
+pub struct ParseErrors(Arc<AtomicUsize>);
+
+impl ParseErrors {
+    pub fn count(&self) -> usize {
+        self.0.load(AtomicOrdering::Relaxed)
+    }
+
+    fn add(&self) {
+        self.0.fetch_add(1, AtomicOrdering::Relaxed);
+    }
+}
+
+pub struct SourcedCsvIterator {
+    reader : Reader<File>,
+    path : Arc<str>,
+    headers : StringRecord,
+    record : StringRecord,
+    parse_errors : ParseErrors,
+}
+
+impl Iterator for SourcedCsvIterator {
+    type Item = SourcedTransaction;
+
+    /// returns None when there are no more records in the file or the
+    /// file itself cannot be read any further. A malformed line, e.g. one
+    /// with too many fields, is reported and skipped like any other line
+    /// that cannot be parsed
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.reader.read_record(&mut self.record) {
+                Ok(true) => {},
+                Ok(false) => return None,
+                Err(e) if e.is_io_error() => {
+                    eprintln!("Error in reading {}, {}", self.path, e);
+                    return None;
+                },
+                Err(e) => {
+                    eprintln!("Error in reading {}, {}", self.path, e);
+                    self.parse_errors.add();
+                    continue;
+                },
+            }
+
+            let provenance = Provenance {
+                source : self.path.clone(),
+                line : self.record.position().map(|p| p.line()).unwrap_or(0),
+            };
+
+            match self.record.deserialize::<CsvTransaction>(Some(&self.headers)) {
+                Ok(CsvTransaction(transaction)) => return Some(SourcedTransaction { provenance, transaction }),
+                Err(e) => {
+                    eprintln!("Error in parsing transaction at {}, {}", provenance, e);
+                    self.parse_errors.add();
+                },
+            }
+        }
+    }
+}
+
+/// `parse_record` splits a single csv line into its fields, for sources
+/// that receive their records one line at a time
+pub(crate) fn parse_record(line : &str) -> crate::Result<StringRecord> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(line.as_bytes());
+
+    let mut record = StringRecord::new();
+    reader.read_record(&mut record)?;
+    Ok(record)
+}
+
+/// `InputOrder` decides how transactions from multiple files are combined
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputOrder {
+    /// each file is processed completely before the next one
+    Sequential,
+    /// files are k-way merged by timestamp, falling back to the
+    /// transaction id as a sequence number
+    Merged,
+}
+
+/// `MultiFileReader` reads transactions from a number of csv files and
+/// provides a single iterator over all of them
+pub struct MultiFileReader {
+    readers : Vec<CsvFileReader>,
+    parse_errors : ParseErrors,
+}
+
+impl MultiFileReader {
+    pub fn new(paths : &[PathBuf]) -> crate::Result<Self> {
+        let mut readers = Vec::with_capacity(paths.len());
+        for path in paths {
+            let path = path.to_str().ok_or(format!("Invalid file name {:?}", path))?;
+            readers.push(CsvFileReader::new(&path.to_string())?);
+        }
+
+        Ok(MultiFileReader { readers, parse_errors : ParseErrors::default() })
+    }
+
+    /// the count of lines skipped by the iterator because they could not
+    /// be parsed, across all files
+    pub fn parse_errors(&self) -> ParseErrors {
+        self.parse_errors.clone()
+    }
+
+    /// returns an iterator over the transactions of all files in the
+    /// given order
+    pub fn iter(self, order : InputOrder) -> crate::Result<Box<dyn Iterator<Item = SourcedTransaction>>> {
+        let mut sources = Vec::with_capacity(self.readers.len());
+        for reader in self.readers {
+            let mut source = reader.into_sourced()?;
+            source.parse_errors = self.parse_errors.clone();
+            sources.push(source);
+        }
+
+        Ok(match order {
+            InputOrder::Sequential => Box::new(sources.into_iter().flatten()),
+            InputOrder::Merged => Box::new(MergedIterator::new(sources)),
+        })
+    }
+}
+
+/// `expand_inputs` converts the inputs given on the command line into a list
+/// of files. An input can be a file, a directory (all csv files in it are
+/// used in name order) or a glob pattern such as `shards/2021-*.csv`
+pub fn expand_inputs(inputs : &[String]) -> crate::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for input in inputs {
+        let path = Path::new(input);
+        if path.is_dir() {
+            let mut dir_files = Vec::new();
+            for entry in path.read_dir()? {
+                let entry_path = entry?.path();
+                if entry_path.is_file() && entry_path.extension().is_some_and(|e| e == "csv") {
+                    dir_files.push(entry_path);
+                }
+            }
+            dir_files.sort();
+            files.extend(dir_files);
+        }
+        else if input.contains(['*', '?', '[']) {
+            let matched = glob::glob(input)?
+                .collect::<std::result::Result<Vec<PathBuf>, _>>()?;
+            if matched.is_empty() {
+                return Err(format!("No files matched {}", input).into());
+            }
+            files.extend(matched);
+        }
+        else {
+            files.push(path.to_path_buf());
+        }
+    }
+
+    if files.is_empty() {
+        return Err("Missing file name to process".into());
+    }
+
+    Ok(files)
+}
+
+/// `MergedIterator` does a k-way merge of a number of sources. Each source
+/// is expected to be in order, so only the head of each one is compared.
+///
+/// A record without a timestamp takes the last timestamp seen in its
+/// source, so it keeps its place relative to its neighbours. Ties are broken
+/// by the transaction id and then by the order in which the sources were given.
+pub struct MergedIterator {
+    sources : Vec<SourcedCsvIterator>,
+    last_timestamp : Vec<Timestamp>,
+    heads : BinaryHeap<Reverse<MergeHead>>,
+}
+
+struct MergeHead {
+    key : (Timestamp, TransactionId, usize),
+    item : SourcedTransaction,
+}
+
+impl PartialEq for MergeHead {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for MergeHead {}
+
+impl PartialOrd for MergeHead {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MergeHead {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+impl MergedIterator {
+    fn new(sources : Vec<SourcedCsvIterator>) -> Self {
+        let mut merged = MergedIterator {
+            last_timestamp : vec![0; sources.len()],
+            sources,
+            heads : BinaryHeap::new(),
+        };
+
+        for index in 0..merged.sources.len() {
+            merged.pull(index);
+        }
+
+        merged
+    }
+
+    /// reads the next record of the given source onto the heap
+    fn pull(&mut self, index : usize) {
+        if let Some(item) = self.sources[index].next() {
+            let timestamp = item.transaction.timestamp.unwrap_or(self.last_timestamp[index]);
+            self.last_timestamp[index] = timestamp;
+
+            let key = (timestamp, item.transaction.tx, index);
+            self.heads.push(Reverse(MergeHead { key, item }));
+        }
+    }
+}
+
+impl Iterator for MergedIterator {
+    type Item = SourcedTransaction;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse(head) = self.heads.pop()?;
+        self.pull(head.key.2);
+        Some(head.item)
+    }
+}
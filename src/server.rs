@@ -0,0 +1,171 @@
+//! `txengine::server::TcpServer`
+//!
+//! Runs the engine as a long lived process that accepts transactions over a
+//! TCP socket. Each connection can send any number of lines and gets one
+//! reply line for every line it sends:
+//!
+//! |Request|Reply|
+//! |-|-|
+//! |`deposit,1,1,1.5`|`OK` or `ERR <reason>`|
+//! |`{"type":"deposit","client":1,"tx":1,"amount":1.5}`|`OK` or `ERR <reason>`|
+//! |`BALANCE 1`|`OK 1,,1.5000,0.0000,1.5000,false` or `ERR <reason>`|
+//! |`BALANCE 1 EUR`|`OK 1,EUR,1.50,0.00,1.50,false` or `ERR <reason>`|
+//!
+//! Csv lines have the columns `type,client,tx,amount,timestamp` in that order
+//! unless the connection first sends a header line starting with `type`.
+//! All connections share a single `TransactionEngine`.
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use csv::StringRecord;
+
+use crate::readers::parse_record;
+use crate::transaction::{ClientId, CsvTransaction, Transaction, TransactionEngine};
+use crate::transaction::currency::Currency;
+
+/// columns expected in a csv line when no header has been sent
+const DEFAULT_COLUMNS : [&str; 5] = ["type", "client", "tx", "amount", "timestamp"];
+
+pub struct TcpServer {
+    listener : TcpListener,
+    engine : Arc<Mutex<TransactionEngine>>,
+}
+
+impl TcpServer {
+    pub fn bind<A : ToSocketAddrs>(addr : A, engine : Arc<Mutex<TransactionEngine>>) -> crate::Result<Self> {
+        Ok(
+            TcpServer {
+                listener : TcpListener::bind(addr)?,
+                engine,
+            }
+        )
+    }
+
+    /// the address the server is listening on, useful when bound to port 0
+    pub fn local_addr(&self) -> crate::Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// accepts connections until the listener fails. Each connection is
+    /// served on its own thread
+    pub fn run(&self) -> crate::Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            // replies are small and sent one per line, so don't wait to batch them
+            stream.set_nodelay(true)?;
+            let engine = self.engine.clone();
+
+            thread::spawn(move || {
+                if let Err(e) = serve_connection(stream, &engine) {
+                    eprintln!("Error in serving connection, {}", e);
+                }
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// reads lines from the connection until it is closed, replying to each one
+fn serve_connection(stream : TcpStream, engine : &Mutex<TransactionEngine>) -> crate::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let reader = BufReader::new(stream);
+    let mut session = Session::new();
+
+    for line in reader.lines() {
+        if let Some(reply) = session.handle_line(&line?, engine) {
+            writeln!(writer, "{}", reply)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// `Session` keeps the state of a single connection, i.e. the csv header
+pub struct Session {
+    headers : StringRecord,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Session {
+            headers : StringRecord::from(DEFAULT_COLUMNS.to_vec()),
+        }
+    }
+
+    /// handles a single line of the protocol and returns the reply. Blank
+    /// lines do not get a reply
+    pub fn handle_line(&mut self, line : &str, engine : &Mutex<TransactionEngine>) -> Option<String> {
+        let line = line.trim();
+        if line.is_empty() {
+            return None;
+        }
+
+        let result = if let Some(client) = line.strip_prefix("BALANCE") {
+            balance(client, engine)
+        }
+        else if line.starts_with("type") {
+            self.set_headers(line).map(|_| String::from("OK"))
+        }
+        else {
+            self.parse(line)
+                .and_then(|transaction| apply(transaction, engine))
+                .map(|_| String::from("OK"))
+        };
+
+        Some(match result {
+            Ok(reply) => reply,
+            Err(e) => format!("ERR {}", e),
+        })
+    }
+
+    fn set_headers(&mut self, line : &str) -> crate::Result<()> {
+        self.headers = parse_record(line)?;
+        Ok(())
+    }
+
+    /// a line is either a json object or a csv record
+    fn parse(&self, line : &str) -> crate::Result<Transaction> {
+        if line.starts_with('{') {
+            return Ok(serde_json::from_str(line)?);
+        }
+
+        let record = parse_record(line)?;
+        let headers : StringRecord = self.headers.iter().take(record.len()).collect();
+        let CsvTransaction(transaction) = record.deserialize(Some(&headers))?;
+        Ok(transaction)
+    }
+}
+
+fn apply(transaction : Transaction, engine : &Mutex<TransactionEngine>) -> crate::Result<()> {
+    let mut engine = engine.lock().map_err(|_| "Engine is unavailable")?;
+    engine.apply(transaction)
+}
+
+/// replies with the balance of the client, in the default currency unless
+/// one is given, as a csv record
+fn balance(args : &str, engine : &Mutex<TransactionEngine>) -> crate::Result<String> {
+    let mut args = args.split_whitespace();
+    let client = args.next().ok_or("Missing client")?.parse::<ClientId>()?;
+    let currency = args.next().map(str::parse::<Currency>).transpose()?.unwrap_or_default();
+
+    let engine = engine.lock().map_err(|_| "Engine is unavailable")?;
+    let ledger = engine.get_ledger(client).ok_or(format!("Client {} not found", client))?;
+    let balance = ledger.balance_in(currency).ok_or(format!("Client {} has no {} balance", client, currency))?;
+
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new());
+    writer.serialize(balance)?;
+
+    let record = String::from_utf8(writer.into_inner()?)?;
+    Ok(format!("OK {}", record.trim_end()))
+}
@@ -0,0 +1,132 @@
+//! `txengine::stream`
+//!
+//! Async counterparts of `process_reader` and `CsvFileReader` for services
+//! that run the engine inside a tokio runtime. Only available with the
+//! `async` feature.
+//!
+//! The engine pulls one transaction at a time out of the stream, so when the
+//! stream is fed from a bounded channel the producers are held back until the
+//! engine has caught up. As applying a transaction never waits on anything,
+//! the engine yields back to the runtime every `YIELD_EVERY` transactions so
+//! that a long stream does not starve other tasks on the same thread.
+use std::sync::Arc;
+
+use csv::StringRecord;
+use futures::stream::{self, Stream, StreamExt};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, Lines};
+
+use crate::readers::{parse_record, Provenance, SourcedTransaction};
+use crate::transaction::{CsvTransaction, Transaction, TransactionEngine};
+
+/// number of transactions applied before yielding back to the runtime
+pub const YIELD_EVERY : usize = 256;
+
+/// `process_stream` takes a stream of transactions and applies each of them
+/// onto a new engine. Transactions that cannot be applied are passed on to
+/// `on_rejected`
+///
+/// Returns the TransactionEngine that holds the ending balances
+/// of all customers after processing the stream
+pub async fn process_stream<S, F>(transactions : S, on_rejected : F) -> TransactionEngine
+    where
+        S : Stream<Item = Transaction>,
+        F : FnMut(crate::Error)
+{
+    let mut engine = TransactionEngine::new();
+    apply_stream(&mut engine, transactions, on_rejected).await;
+    engine
+}
+
+/// `apply_stream` applies each transaction of the stream onto an existing
+/// engine
+pub async fn apply_stream<S, F>(engine : &mut TransactionEngine, transactions : S, mut on_rejected : F)
+    where
+        S : Stream<Item = Transaction>,
+        F : FnMut(crate::Error)
+{
+    let mut transactions = std::pin::pin!(transactions);
+    let mut applied = 0;
+
+    while let Some(transaction) = transactions.next().await {
+        if let Err(e) = engine.apply(transaction) {
+            on_rejected(e);
+        }
+
+        applied += 1;
+        if applied % YIELD_EVERY == 0 {
+            tokio::task::yield_now().await;
+        }
+    }
+}
+
+/// `AsyncCsvReader` reads csv transactions from an async reader, e.g. a
+/// tokio file or socket wrapped in a `BufReader`.
+///
+/// Records are read line by line, so quoted fields spanning multiple lines
+/// are not supported.
+pub struct AsyncCsvReader<R> {
+    lines : Lines<R>,
+    source : Arc<str>,
+}
+
+impl<R> AsyncCsvReader<R>
+    where
+        R : AsyncBufRead + Unpin
+{
+    /// `source` is used as the file name in the provenance of each transaction
+    pub fn new(reader : R, source : &str) -> Self {
+        AsyncCsvReader {
+            lines : reader.lines(),
+            source : Arc::from(source),
+        }
+    }
+
+    /// returns a stream that provides each Transaction along with the line
+    /// it was read from. The first line is expected to be the header
+    ///
+    /// A line that cannot be parsed is reported and skipped. The stream ends
+    /// when the reader is exhausted or cannot be read any further
+    pub fn into_stream(self) -> impl Stream<Item = SourcedTransaction> {
+        let state = (self, None::<StringRecord>, 0u64);
+
+        stream::unfold(state, |(mut reader, mut headers, mut line)| async move {
+            loop {
+                let text = match reader.lines.next_line().await {
+                    Ok(Some(text)) => text,
+                    Ok(None) => return None,
+                    Err(e) => {
+                        eprintln!("Error in reading {}, {}", reader.source, e);
+                        return None;
+                    }
+                };
+                line += 1;
+
+                if text.trim().is_empty() {
+                    continue;
+                }
+
+                let provenance = Provenance { source : reader.source.clone(), line };
+                let record = match parse_record(&text) {
+                    Ok(record) => record,
+                    Err(e) => {
+                        eprintln!("Error in parsing transaction at {}, {}", provenance, e);
+                        continue;
+                    }
+                };
+
+                let Some(header_record) = &headers else {
+                    headers = Some(record);
+                    continue;
+                };
+
+                match record.deserialize::<CsvTransaction>(Some(header_record)) {
+                    Ok(CsvTransaction(transaction)) => {
+                        let item = SourcedTransaction { provenance, transaction };
+                        return Some((item, (reader, headers, line)));
+                    },
+                    Err(e) => eprintln!("Error in parsing transaction at {}, {}", provenance, e),
+                }
+            }
+        })
+    }
+}
@@ -6,16 +6,21 @@
 //! An equal operator has been defined that also considers to amounts
 //! to be same if they are same till the 4 digit precision
 //! 
+//! An amount does not carry its currency, but it can be rounded to and
+//! formatted with the minor units of one
+//! 
 //! Deref can be used to easily access the internal f32:
 //! let x = Amount::new(0.23);
 //! println!("{}", *x);
 
 use core::str::FromStr;
 use std::fmt;
-use core::ops::{Add, SubAssign, Deref, DerefMut, AddAssign};
+use core::ops::{Add, Sub, SubAssign, Deref, DerefMut, AddAssign};
 use serde::{Serialize};
 use serde::ser::{Serializer};
 
+use super::currency::Currency;
+
 #[derive(Debug, Copy, Clone, PartialOrd)]
 pub struct Amount(f32);
 
@@ -23,6 +28,17 @@ impl Amount {
     pub fn new(init : f32) -> Amount {
         Amount(init)
     }
+
+    /// rounds the amount half away from zero to the minor units of the currency
+    pub fn round_to(self, currency : Currency) -> Amount {
+        let scale = 10f32.powi(currency.minor_units() as i32);
+        Amount((self.0 * scale).round() / scale)
+    }
+
+    /// formats the amount with the minor units of the currency
+    pub fn display_in(self, currency : Currency) -> String {
+        format!("{:.*}", currency.minor_units() as usize, self.0)
+    }
 }
 
 impl fmt::Display for Amount {
@@ -40,6 +56,13 @@ impl Add for Amount {
     }
 }
 
+impl Sub for Amount {
+    type Output = Amount;
+    fn sub(self, rhs: Amount) -> Self::Output {
+        Amount(*self - *rhs)
+    }
+}
+
 impl AddAssign for Amount {
     fn add_assign(&mut self, rhs: Self) {
         **self += *rhs;
@@ -0,0 +1,115 @@
+//! Batches of transactions.
+//!
+//! `TransactionEngine::apply_batch` applies a batch of transactions all or
+//! nothing. While a batch is applied the engine keeps an `UndoLog` of what it
+//! changes: each ledger is saved the first time the batch changes it, along
+//! with the holds that were scheduled or expired and the clock. Rolling back
+//! restores the saved ledgers and removes the ones the batch created, so its
+//! cost is in the clients the batch touched rather than the whole engine. A
+//! batch that moves the clock over a day while interest is configured touches
+//! every client, as interest accrues on all of them.
+//!
+//! The screening is rolled back to a `Screening::snapshot` taken before the
+//! batch, which copies all of its state. A screening without a snapshot is
+//! not rolled back, so a rolled back transaction still counts towards e.g.
+//! the disputes of its client.
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::error::Error;
+use std::fmt;
+
+use super::{ClientId, Timestamp, TransactionEngine, TransactionId};
+use super::ledger::ClientLedger;
+use super::screening::Screening;
+
+/// a hold expiry, as kept by the engine
+pub(crate) type Expiry = (Timestamp, ClientId, TransactionId);
+
+/// `UndoLog` is what a batch changed in the engine
+#[derive(Debug)]
+pub(crate) struct UndoLog {
+    /// ledgers as they were before the batch changed them, none for the
+    /// ledgers that the batch created
+    ledgers : HashMap<ClientId, Option<ClientLedger>>,
+    scheduled : Vec<Expiry>,
+    expired : Vec<Expiry>,
+    clock : Option<Timestamp>,
+    accrued_to : Option<Timestamp>,
+    flagged : usize,
+    screening : Option<Box<dyn Screening>>,
+}
+
+impl UndoLog {
+    pub(crate) fn new(engine : &TransactionEngine) -> Self {
+        UndoLog {
+            ledgers : HashMap::new(),
+            scheduled : Vec::new(),
+            expired : Vec::new(),
+            clock : engine.clock,
+            accrued_to : engine.accrued_to,
+            flagged : engine.flagged.len(),
+            screening : engine.screening.as_ref().and_then(|screening| screening.snapshot()),
+        }
+    }
+
+    /// saves the ledger of the client, unless it has been saved already
+    pub(crate) fn save(&mut self, ledgers : &HashMap<ClientId, ClientLedger>, client : ClientId) {
+        self.ledgers.entry(client).or_insert_with(|| ledgers.get(&client).cloned());
+    }
+
+    pub(crate) fn scheduled(&mut self, expiry : Expiry) {
+        self.scheduled.push(expiry);
+    }
+
+    pub(crate) fn expired(&mut self, expiry : Expiry) {
+        self.expired.push(expiry);
+    }
+
+    /// puts the engine back in the state it was in before the batch
+    pub(crate) fn roll_back(self, engine : &mut TransactionEngine) {
+        for (client, ledger) in self.ledgers {
+            match ledger {
+                Some(ledger) => engine.ledger.insert(client, ledger),
+                None => engine.ledger.remove(&client),
+            };
+        }
+
+        let mut expiries = std::mem::take(&mut engine.expiries).into_vec();
+        for expiry in self.scheduled {
+            if let Some(at) = expiries.iter().position(|Reverse(scheduled)| *scheduled == expiry) {
+                expiries.swap_remove(at);
+            }
+        }
+        expiries.extend(self.expired.into_iter().map(Reverse));
+        engine.expiries = BinaryHeap::from(expiries);
+
+        engine.clock = self.clock;
+        engine.accrued_to = self.accrued_to;
+        engine.flagged.truncate(self.flagged);
+        if let Some(screening) = self.screening {
+            engine.screening = Some(screening);
+        }
+    }
+}
+
+/// `BatchError` is returned when a transaction of a batch fails, after the
+/// batch has been rolled back. The error of the transaction is its source
+#[derive(Debug)]
+pub struct BatchError {
+    /// the position of the transaction in the batch
+    pub index : usize,
+    pub tx : TransactionId,
+    pub error : crate::Error,
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Batch rolled back, transaction {} at {} failed, {}", self.tx, self.index, self.error)
+    }
+}
+
+impl Error for BatchError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
@@ -0,0 +1,208 @@
+//! `EngineConfig` holds the settings that change how the engine applies
+//! transactions. The default config applies no fees, blocks everything
+//! on a locked account, disputes withdrawals like deposits and only lets
+//! disputes take the available funds below zero. Without exchange rates
+//! there are no conversions, no interest is earned and withdrawals have no
+//! limits.
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use super::{ClientId, TransactionType};
+use super::amount::Amount;
+use super::fees::FeeSchedule;
+use super::interest::InterestSchedule;
+use super::limits::WithdrawalLimits;
+use super::rates::RateTable;
+
+/// types of transactions that a lock policy can allow, administrative
+/// transactions are always applied on a locked account
+const LOCKABLE : [&str; 10] = ["deposit", "withdrawal", "dispute", "resolve", "chargeback", "transfer", "fee", "hold", "release", "convert"];
+
+#[derive(Debug, Clone, Default)]
+pub struct EngineConfig {
+    pub fees : FeeSchedule,
+    pub lock_policy : LockPolicy,
+    pub withdrawal_disputes : WithdrawalDisputes,
+    pub balance_policy : BalancePolicy,
+    pub rates : RateTable,
+    pub interest : Option<InterestSchedule>,
+    pub withdrawal_limits : WithdrawalLimits,
+}
+
+impl EngineConfig {
+    pub fn with_fees(mut self, fees : FeeSchedule) -> Self {
+        self.fees = fees;
+        self
+    }
+
+    pub fn with_lock_policy(mut self, lock_policy : LockPolicy) -> Self {
+        self.lock_policy = lock_policy;
+        self
+    }
+
+    pub fn with_withdrawal_disputes(mut self, withdrawal_disputes : WithdrawalDisputes) -> Self {
+        self.withdrawal_disputes = withdrawal_disputes;
+        self
+    }
+
+    pub fn with_balance_policy(mut self, balance_policy : BalancePolicy) -> Self {
+        self.balance_policy = balance_policy;
+        self
+    }
+
+    pub fn with_rates(mut self, rates : RateTable) -> Self {
+        self.rates = rates;
+        self
+    }
+
+    pub fn with_interest(mut self, interest : InterestSchedule) -> Self {
+        self.interest = Some(interest);
+        self
+    }
+
+    pub fn with_withdrawal_limits(mut self, withdrawal_limits : WithdrawalLimits) -> Self {
+        self.withdrawal_limits = withdrawal_limits;
+        self
+    }
+}
+
+/// `Debit` is why funds are taken from the available funds of a client
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Debit {
+    /// asked for by the client, i.e. withdrawals, transfers and fee transactions
+    Requested,
+    /// imposed by a dispute, i.e. funds held by a dispute and chargeback fees
+    Imposed,
+}
+
+/// `BalancePolicy` is how far the available funds of a client can go below
+/// zero. Operator adjustments are not bound by it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum BalancePolicy {
+    /// the available funds never go below zero
+    Strict,
+    /// only disputes can take the available funds below zero
+    #[default]
+    NegativeFromDisputes,
+    /// the available funds can go below zero up to the limit of the client,
+    /// clients without a limit cannot go below zero
+    Overdraft(HashMap<ClientId, Amount>),
+}
+
+/// a single line of an overdraft limits file
+#[derive(Debug, Deserialize)]
+struct OverdraftRecord {
+    client : ClientId,
+    limit : f32,
+}
+
+impl BalancePolicy {
+    /// `parse` reads `strict` or `disputes`, overdrafts are loaded with
+    /// [`BalancePolicy::overdraft_from_csv`]
+    pub fn parse(name : &str) -> crate::Result<Self> {
+        match name {
+            "strict" => Ok(BalancePolicy::Strict),
+            "disputes" => Ok(BalancePolicy::NegativeFromDisputes),
+            other => Err(format!("Unknown balance policy {}", other).into()),
+        }
+    }
+
+    /// `overdraft_from_csv` loads the overdraft limits of clients from a csv
+    /// file with the columns `client,limit`
+    pub fn overdraft_from_csv(path : &str) -> crate::Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_path(path)?;
+
+        let mut limits = HashMap::new();
+        for record in reader.deserialize::<OverdraftRecord>() {
+            let record = record?;
+            if record.limit < 0.0 {
+                return Err(format!("Overdraft limit of client {} is negative", record.client).into());
+            }
+            limits.insert(record.client, Amount::new(record.limit));
+        }
+
+        Ok(BalancePolicy::Overdraft(limits))
+    }
+
+    /// the lowest the available funds of the client can go by the given
+    /// debit, `None` when there is no limit
+    pub fn floor(&self, client : ClientId, debit : Debit) -> Option<Amount> {
+        match (self, debit) {
+            (BalancePolicy::Strict, _) => Some(Amount::new(0.0)),
+            (BalancePolicy::NegativeFromDisputes, Debit::Requested) => Some(Amount::new(0.0)),
+            (BalancePolicy::NegativeFromDisputes, Debit::Imposed) => None,
+            (BalancePolicy::Overdraft(limits), _) => {
+                Some(Amount::new(-*limits.get(&client).copied().unwrap_or(Amount::new(0.0))))
+            },
+        }
+    }
+}
+
+/// `WithdrawalDisputes` is how funds move when a past withdrawal is disputed
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum WithdrawalDisputes {
+    /// disputed the same way as a deposit, the amount is moved from available
+    /// to held and a chargeback removes it from held
+    #[default]
+    AsDeposit,
+    /// the withdrawn amount is provisionally credited to held. A resolve
+    /// removes the credit while a chargeback moves it to available
+    ProvisionalCredit,
+    /// withdrawals cannot be disputed
+    Rejected,
+}
+
+impl WithdrawalDisputes {
+    /// `parse` reads `deposit`, `provisional` or `reject`
+    pub fn parse(name : &str) -> crate::Result<Self> {
+        match name {
+            "deposit" => Ok(WithdrawalDisputes::AsDeposit),
+            "provisional" => Ok(WithdrawalDisputes::ProvisionalCredit),
+            "reject" => Ok(WithdrawalDisputes::Rejected),
+            other => Err(format!("Unknown withdrawal dispute semantics {}", other).into()),
+        }
+    }
+}
+
+/// `LockPolicy` is the transaction types, by name, that are still applied
+/// once an account is locked. By default nothing is allowed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LockPolicy {
+    allowed : Vec<&'static str>,
+}
+
+impl LockPolicy {
+    /// blocks every transaction on a locked account
+    pub fn block_all() -> Self {
+        LockPolicy::default()
+    }
+
+    /// allows the given type of transaction, e.g. `resolve`, on a locked account
+    pub fn allow(mut self, name : &str) -> crate::Result<Self> {
+        let name = LOCKABLE.iter()
+            .find(|lockable| **lockable == name)
+            .ok_or(format!("{} cannot be allowed on a locked account", name))?;
+
+        if !self.allowed.contains(name) {
+            self.allowed.push(name);
+        }
+        Ok(self)
+    }
+
+    /// `parse` reads a comma separated list of transaction types,
+    /// e.g. `deposit,resolve,chargeback`
+    pub fn parse(list : &str) -> crate::Result<Self> {
+        list.split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .try_fold(LockPolicy::block_all(), |policy, name| policy.allow(name))
+    }
+
+    /// whether the transaction is applied on a locked account
+    pub fn allows(&self, txn_type : &TransactionType) -> bool {
+        txn_type.is_administrative() || self.allowed.contains(&txn_type.name())
+    }
+}
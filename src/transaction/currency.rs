@@ -0,0 +1,83 @@
+//! Currencies of amounts.
+//!
+//! A `Currency` is a three letter code such as `EUR`, or the default
+//! currency of transactions that do not give one. Amounts of a currency are
+//! kept to its minor units, e.g. 2 decimals for cents. The default currency
+//! keeps the 4 decimals that all amounts had before currencies were added.
+use core::str::FromStr;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Currency(Option<[u8; 3]>);
+
+impl Currency {
+    /// the currency of transactions that do not give one
+    pub const DEFAULT : Currency = Currency(None);
+
+    pub fn is_default(&self) -> bool {
+        self.0.is_none()
+    }
+
+    /// the three letter code, empty for the default currency
+    pub fn code(&self) -> &str {
+        match &self.0 {
+            Some(code) => std::str::from_utf8(code).unwrap_or(""),
+            None => "",
+        }
+    }
+
+    /// number of decimals that amounts of the currency are kept to
+    pub fn minor_units(&self) -> u32 {
+        match self.code() {
+            "" => 4,
+            "JPY" | "KRW" | "ISK" | "CLP" | "VND" => 0,
+            "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+            _ => 2,
+        }
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl FromStr for Currency {
+    type Err = String;
+
+    fn from_str(value : &str) -> Result<Self, Self::Err> {
+        let code : [u8; 3] = value.as_bytes()
+            .try_into()
+            .ok()
+            .filter(|code : &[u8; 3]| code.iter().all(u8::is_ascii_alphabetic))
+            .ok_or(format!("{} is not a three letter currency code", value))?;
+
+        Ok(Currency(Some(code.map(|c| c.to_ascii_uppercase()))))
+    }
+}
+
+impl Serialize for Currency {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        serializer.serialize_str(self.code())
+    }
+}
+
+/// reads a three letter code, or an empty string for the default currency
+impl<'de> Deserialize<'de> for Currency {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        let code = String::deserialize(deserializer)?;
+        if code.is_empty() {
+            return Ok(Currency::DEFAULT);
+        }
+        code.parse().map_err(de::Error::custom)
+    }
+}
@@ -0,0 +1,233 @@
+//! Fees charged by the engine.
+//!
+//! A `FeeSchedule` says what is charged alongside withdrawals and
+//! chargebacks. Each `Fee` is a flat amount, a percentage, or tiers of those
+//! chosen by the amount of the transaction, optionally capped by a minimum
+//! and a maximum, each tier having its own caps. Flat amounts and caps are
+//! in the currency of the transaction, so a currency with other minor units
+//! can be given its own fees. Every fee charged is kept as a `FeeEntry` in the
+//! client's ledger so that it can be reported separately from the transaction.
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use super::TransactionId;
+use super::amount::Amount;
+use super::currency::Currency;
+
+/// `FeeRule` decides the fee for a given transaction amount
+#[derive(Debug, Clone, PartialEq)]
+pub enum FeeRule {
+    Flat(Amount),
+    /// percentage of the transaction amount, e.g. 1.5 for 1.5%
+    Percentage(f32),
+    /// tiers of (threshold, fee). The fee of the highest threshold that
+    /// the transaction amount reaches is used, no fee below the lowest one
+    Tiered(Vec<(Amount, Fee)>),
+}
+
+impl FeeRule {
+    fn compute(&self, amount : Amount) -> Amount {
+        match self {
+            FeeRule::Flat(fee) => *fee,
+            FeeRule::Percentage(percent) => Amount::new(*amount * percent / 100.0),
+            FeeRule::Tiered(tiers) => {
+                tiers.iter()
+                    .filter(|(threshold, _)| *threshold <= amount)
+                    .max_by(|(a, _), (b, _)| a.total_cmp(b))
+                    .map(|(_, fee)| fee.compute(amount))
+                    .unwrap_or(Amount::new(0.0))
+            },
+        }
+    }
+}
+
+/// `Fee` is a rule along with optional caps on what it can charge
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fee {
+    pub rule : FeeRule,
+    pub min : Option<Amount>,
+    pub max : Option<Amount>,
+}
+
+impl Fee {
+    pub fn new(rule : FeeRule) -> Self {
+        Fee {
+            rule,
+            min : None,
+            max : None,
+        }
+    }
+
+    pub fn with_min(mut self, min : Amount) -> Self {
+        self.min = Some(min);
+        self
+    }
+
+    pub fn with_max(mut self, max : Amount) -> Self {
+        self.max = Some(max);
+        self
+    }
+
+    /// returns the fee for a transaction of the given amount, rounded to
+    /// four decimal places
+    pub fn compute(&self, amount : Amount) -> Amount {
+        let mut fee = *self.rule.compute(amount);
+
+        if let Some(min) = self.min {
+            fee = fee.max(*min);
+        }
+        if let Some(max) = self.max {
+            fee = fee.min(*max);
+        }
+
+        Amount::new((fee * 10000.0).round() / 10000.0)
+    }
+}
+
+/// `FeeSchedule` holds the fees the engine charges automatically
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FeeSchedule {
+    pub withdrawal : Option<Fee>,
+    pub chargeback : Option<Fee>,
+    /// fees of the transactions in a currency, in place of the ones above
+    pub currencies : HashMap<Currency, FeeSchedule>,
+}
+
+/// a single line of a fee schedule file
+#[derive(Debug, Deserialize)]
+struct FeeScheduleRecord {
+    applies_to : String,
+    rule : String,
+    value : f32,
+    from : Option<f32>,
+    min : Option<f32>,
+    max : Option<f32>,
+    currency : Option<Currency>,
+}
+
+impl FeeSchedule {
+    /// gives the transactions in the currency their own fees
+    pub fn with_currency(mut self, currency : Currency, fees : FeeSchedule) -> Self {
+        self.currencies.insert(currency, fees);
+        self
+    }
+
+    /// the fee of a withdrawal in the currency
+    pub fn withdrawal_in(&self, currency : Currency) -> Option<&Fee> {
+        self.currencies.get(&currency)
+            .and_then(|fees| fees.withdrawal.as_ref())
+            .or(self.withdrawal.as_ref())
+    }
+
+    /// the fee of a chargeback in the currency
+    pub fn chargeback_in(&self, currency : Currency) -> Option<&Fee> {
+        self.currencies.get(&currency)
+            .and_then(|fees| fees.chargeback.as_ref())
+            .or(self.chargeback.as_ref())
+    }
+
+    /// `from_csv` loads a schedule from a csv file with the columns
+    /// `applies_to,rule,value,from,min,max` and an optional `currency`, e.g.
+    ///
+    /// ```text
+    /// applies_to,rule,value,from,min,max,currency
+    /// withdrawal,percentage,0.5,,0.1,25,
+    /// chargeback,flat,15,,,,
+    /// chargeback,flat,2000,,,,JPY
+    /// ```
+    ///
+    /// `applies_to` is `withdrawal` or `chargeback`, `rule` is `flat` or
+    /// `percentage`. Giving `from` on the lines of a fee makes it tiered, each
+    /// line being the tier starting at that amount. `min` and `max` cap the fee,
+    /// or the tier of their line. A line with a `currency` is the fee of the
+    /// transactions in that currency, the others apply to all the rest. None of
+    /// the amounts can be negative.
+    pub fn from_csv(path : &str) -> crate::Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_path(path)?;
+
+        let mut schedule = FeeSchedule::default();
+        for record in reader.deserialize::<FeeScheduleRecord>() {
+            let record = record?;
+            let negative = [Some(record.value), record.from, record.min, record.max].into_iter()
+                .flatten()
+                .any(|value| value < 0.0);
+            if negative {
+                return Err(format!("A {} fee cannot have a negative amount", record.applies_to).into());
+            }
+            if let (Some(min), Some(max)) = (record.min, record.max) {
+                if min > max {
+                    return Err(format!("A {} fee cannot have a min of {} over its max of {}", record.applies_to, min, max).into());
+                }
+            }
+
+            let rule = match record.rule.as_str() {
+                "flat" => FeeRule::Flat(Amount::new(record.value)),
+                "percentage" => FeeRule::Percentage(record.value),
+                other => return Err(format!("Unknown fee rule {}", other).into()),
+            };
+
+            let fees = match record.currency {
+                Some(currency) => schedule.currencies.entry(currency).or_default(),
+                None => &mut schedule,
+            };
+            let slot = match record.applies_to.as_str() {
+                "withdrawal" => &mut fees.withdrawal,
+                "chargeback" => &mut fees.chargeback,
+                other => return Err(format!("Fees cannot be applied to {}", other).into()),
+            };
+
+            // the caps of the line are those of the fee, or of its tier
+            let mut fee = Fee::new(rule);
+            fee.min = record.min.map(Amount::new);
+            fee.max = record.max.map(Amount::new);
+
+            let fee = match (slot.take(), record.from) {
+                (None, None) => fee,
+                (None, Some(from)) => Fee::new(FeeRule::Tiered(vec![(Amount::new(from), fee)])),
+                (Some(Fee { rule : FeeRule::Tiered(mut tiers), min, max }), Some(from)) => {
+                    tiers.push((Amount::new(from), fee));
+                    Fee { rule : FeeRule::Tiered(tiers), min, max }
+                },
+                (Some(_), _) => {
+                    return Err(format!("More than one {} fee, use `from` for tiers", record.applies_to).into());
+                },
+            };
+
+            *slot = Some(fee);
+        }
+
+        Ok(schedule)
+    }
+}
+
+/// `FeeSource` is what caused a fee to be charged
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FeeSource {
+    /// a fee transaction in the input
+    Fee,
+    Withdrawal,
+    ChargeBack,
+}
+
+impl FeeSource {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FeeSource::Fee => "fee",
+            FeeSource::Withdrawal => "withdrawal",
+            FeeSource::ChargeBack => "chargeback",
+        }
+    }
+}
+
+/// `FeeEntry` is a fee charged to a client, `tx` being the transaction that
+/// caused it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FeeEntry {
+    pub tx : TransactionId,
+    pub source : FeeSource,
+    pub amount : Amount,
+    pub currency : Currency,
+}
@@ -0,0 +1,147 @@
+//! Interest on available funds.
+//!
+//! An `InterestSchedule` gives the yearly rate earned on the available funds
+//! of a balance, flat or tiered by the available amount, and how often the
+//! interest is posted. Interest accrues for every whole day the engine's
+//! clock moves past, at 1/365 of the yearly rate of the available funds
+//! during that day, and is not compounded within a period.
+//!
+//! Periods start at multiples of the posting period since the unix epoch.
+//! At the end of a period the accrued interest of each balance is rounded
+//! half away from zero to the minor units of its currency and credited to
+//! the available funds as an `InterestEntry`. What is lost by the rounding
+//! is not carried over to the next period.
+use serde::Deserialize;
+
+use super::Timestamp;
+use super::amount::Amount;
+use super::currency::Currency;
+
+/// seconds in a day
+pub const DAY : Timestamp = 24 * 60 * 60;
+
+/// `InterestRule` decides the yearly rate, in percent, for an available amount
+#[derive(Debug, Clone, PartialEq)]
+pub enum InterestRule {
+    Flat(f64),
+    /// tiers of (threshold, rate). The rate of the highest threshold that the
+    /// available amount reaches applies to all of it, no interest below the
+    /// lowest one
+    Tiered(Vec<(Amount, f64)>),
+}
+
+impl InterestRule {
+    /// the yearly rate, in percent, earned by the available amount
+    pub fn rate(&self, available : Amount) -> Option<f64> {
+        match self {
+            InterestRule::Flat(rate) => Some(*rate),
+            InterestRule::Tiered(tiers) => {
+                tiers.iter()
+                    .filter(|(threshold, _)| *threshold <= available)
+                    .max_by(|(a, _), (b, _)| a.total_cmp(b))
+                    .map(|(_, rate)| *rate)
+            },
+        }
+    }
+}
+
+/// `InterestSchedule` is the interest earned by all balances and how often
+/// it is posted
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterestSchedule {
+    pub rule : InterestRule,
+    /// length of a posting period in days
+    pub period_days : u64,
+}
+
+/// a single line of an interest rates file
+#[derive(Debug, Deserialize)]
+struct InterestRecord {
+    from : Option<f32>,
+    rate : f64,
+}
+
+impl InterestSchedule {
+    /// interest posted every 30 days
+    pub fn new(rule : InterestRule) -> Self {
+        InterestSchedule {
+            rule,
+            period_days : 30,
+        }
+    }
+
+    pub fn with_period_days(mut self, period_days : u64) -> crate::Result<Self> {
+        if period_days == 0 {
+            return Err("Interest period must be at least a day".into());
+        }
+        self.period_days = period_days;
+        Ok(self)
+    }
+
+    /// `from_csv` loads the rates from a csv file with the columns `from,rate`.
+    /// A single line without `from` is a flat rate, otherwise each line is the
+    /// tier starting at that available amount
+    pub fn from_csv(path : &str) -> crate::Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_path(path)?;
+
+        let mut rule : Option<InterestRule> = None;
+        for record in reader.deserialize::<InterestRecord>() {
+            let record = record?;
+            rule = match (rule, record.from) {
+                (None, None) => Some(InterestRule::Flat(record.rate)),
+                (None, Some(from)) => Some(InterestRule::Tiered(vec![(Amount::new(from), record.rate)])),
+                (Some(InterestRule::Tiered(mut tiers)), Some(from)) => {
+                    tiers.push((Amount::new(from), record.rate));
+                    Some(InterestRule::Tiered(tiers))
+                },
+                (Some(_), _) => return Err("More than one interest rate, use `from` for tiers".into()),
+            };
+        }
+
+        let rule = rule.ok_or("No interest rate given")?;
+        Ok(InterestSchedule::new(rule))
+    }
+
+    /// the end of the period that the given time is in
+    pub fn period_end(&self, at : Timestamp) -> Timestamp {
+        let period = self.period_days * DAY;
+        (at / period + 1) * period
+    }
+}
+
+/// `InterestAccrual` is interest accrued over days in which the available
+/// funds did not change
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InterestAccrual {
+    pub from : Timestamp,
+    pub to : Timestamp,
+    pub available : Amount,
+    /// yearly rate in percent
+    pub rate : f64,
+    pub interest : f64,
+}
+
+impl InterestAccrual {
+    pub fn new(from : Timestamp, to : Timestamp, available : Amount, rate : f64) -> Self {
+        let days = ((to - from) / DAY) as f64;
+        InterestAccrual {
+            from,
+            to,
+            available,
+            rate,
+            interest : *available as f64 * rate / 100.0 * days / 365.0,
+        }
+    }
+}
+
+/// `InterestEntry` is interest posted to a balance at the end of a period,
+/// along with how it accrued
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterestEntry {
+    pub posted : Timestamp,
+    pub currency : Currency,
+    pub amount : Amount,
+    pub accruals : Vec<InterestAccrual>,
+}
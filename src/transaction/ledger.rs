@@ -1,27 +1,364 @@
-use std::collections::HashMap;
+use std::collections::{btree_map, BTreeMap, HashMap};
 use serde::ser::{Serializer, SerializeStruct};
 use serde::{Serialize};
 use std::fmt;
 
-use super::{ClientId, TransactionId, Transaction, TransactionType};
+use super::{ClientId, Origin, Timestamp, TransactionId, Transaction, TransactionType};
 use super::amount::Amount;
+use super::config::{Debit, EngineConfig, WithdrawalDisputes};
+use super::currency::Currency;
+use super::rates;
+use super::fees::{Fee, FeeEntry, FeeSource};
+use super::interest::{InterestAccrual, InterestEntry, InterestSchedule};
+use super::limits::{Limit, LimitWindow};
+
+/// `RecordKind` tells how a past transaction moved money in or out of
+/// the account
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordKind {
+    Deposit,
+    Withdrawal,
+    TransferOut { to : ClientId },
+    TransferIn { from : ClientId },
+}
+
+/// `DisputeState` is where a past transaction is in the dispute process
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DisputeState {
+    Settled,
+    Disputed,
+    ChargedBack,
+}
+
+/// `TransactionRecord` is what is remembered of a past transaction for
+/// future disputes and resolutions. A transaction can be disputed in parts,
+/// `disputed` is what is currently disputed and `charged_back` what has
+/// been charged back so far
+#[derive(Debug, Clone, Copy)]
+pub struct TransactionRecord {
+    pub kind : RecordKind,
+    pub amount : Amount,
+    pub state : DisputeState,
+    pub disputed : Amount,
+    pub charged_back : Amount,
+    pub currency : Currency,
+}
+
+impl TransactionRecord {
+    pub fn new(kind : RecordKind, amount : Amount) -> Self {
+        TransactionRecord {
+            kind,
+            amount,
+            state : DisputeState::Settled,
+            disputed : Amount::new(0.0),
+            charged_back : Amount::new(0.0),
+            currency : Currency::DEFAULT,
+        }
+    }
+
+    pub fn with_currency(mut self, currency : Currency) -> Self {
+        self.currency = currency;
+        self
+    }
+
+    /// returns the amount a dispute holds, which is the amount given to the
+    /// dispute or else all that is not yet disputed or charged back
+    pub fn to_dispute(&self, transaction : &Transaction) -> crate::Result<Amount> {
+        partial(transaction, self.amount - self.disputed - self.charged_back)
+    }
+
+    /// returns the amount a resolve or chargeback releases, which is the
+    /// amount given to it or else all that is currently disputed
+    pub fn to_release(&self, transaction : &Transaction) -> crate::Result<Amount> {
+        partial(transaction, self.disputed)
+    }
+
+    pub(crate) fn dispute(&mut self, amount : Amount) {
+        self.disputed += amount;
+        self.update_state();
+    }
+
+    pub(crate) fn resolve(&mut self, amount : Amount) {
+        self.disputed -= amount;
+        self.update_state();
+    }
+
+    pub(crate) fn charge_back(&mut self, amount : Amount) {
+        self.disputed -= amount;
+        self.charged_back += amount;
+        self.update_state();
+    }
+
+    fn update_state(&mut self) {
+        self.state = if self.disputed != 0.0 {
+            DisputeState::Disputed
+        } else if self.charged_back != 0.0 {
+            DisputeState::ChargedBack
+        } else {
+            DisputeState::Settled
+        };
+    }
+}
+
+/// returns the amount given to the transaction, or all that is open, as
+/// long as it is not more than what is open
+fn partial(transaction : &Transaction, open : Amount) -> crate::Result<Amount> {
+    let action = transaction.txn_type.name();
+    if open == 0.0 {
+        return Err(format!("Transaction {} has nothing left to {}", transaction.tx, action).into());
+    }
+
+    let amount = transaction.txn_type.partial_amount().unwrap_or(open);
+    if amount == 0.0 || (amount > open && amount != open) {
+        return Err(format!("Cannot {} {} of transaction {}, {} is open", action, amount, transaction.tx, open).into());
+    }
+
+    Ok(amount)
+}
+
+/// `AdjustmentEntry` is a correction made by an operator along with the
+/// reason given for it
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdjustmentEntry {
+    pub tx : TransactionId,
+    pub amount : Amount,
+    pub currency : Currency,
+    pub reason : String,
+}
+
+/// `HoldEntry` is a hold on the funds of a client that has not been
+/// released yet
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HoldEntry {
+    pub tx : TransactionId,
+    pub amount : Amount,
+    pub currency : Currency,
+    pub expires : Option<Timestamp>,
+}
+
+/// `ConversionEntry` is a conversion between two currencies of a client along
+/// with the rate it was made at
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConversionEntry {
+    pub tx : TransactionId,
+    pub from : Currency,
+    pub to : Currency,
+    pub amount : Amount,
+    pub converted : Amount,
+    pub rate : f64,
+    /// when the rate became effective
+    pub effective : Timestamp,
+}
 
 /// `ClientLedger` keeps record of past deposit / withdrawal transactions
-/// of a client and the current balance of the account
-#[derive(Debug)]
+/// of a client, the fees charged to it, operator adjustments, conversions,
+/// interest, open holds and the current balance of the account in each
+/// currency it uses
+#[derive(Debug, Clone)]
 pub struct ClientLedger {
-    transactions : HashMap<TransactionId, Amount>,
-    balance: ClientBalance
+    transactions : HashMap<TransactionId, TransactionRecord>,
+    fees : Vec<FeeEntry>,
+    adjustments : Vec<AdjustmentEntry>,
+    conversions : Vec<ConversionEntry>,
+    interest : Vec<InterestEntry>,
+    /// interest accrued in each currency since it was last posted
+    accruing : BTreeMap<Currency, Vec<InterestAccrual>>,
+    /// recent withdrawals of a client with limits, for the limits that
+    /// apply over a window
+    withdrawn : Vec<(Timestamp, Currency, Amount)>,
+    holds : HashMap<TransactionId, HoldEntry>,
+    /// balance in the default currency
+    balance: ClientBalance,
+    /// balances in all other currencies
+    foreign : BTreeMap<Currency, ClientBalance>,
 }
 
 impl ClientLedger {
     pub fn new(client: ClientId) -> Self {
         ClientLedger {
             transactions : HashMap::new(),
-            balance : ClientBalance::new(client)
+            fees : Vec::new(),
+            adjustments : Vec::new(),
+            conversions : Vec::new(),
+            interest : Vec::new(),
+            accruing : BTreeMap::new(),
+            withdrawn : Vec::new(),
+            holds : HashMap::new(),
+            balance : ClientBalance::new(client),
+            foreign : BTreeMap::new(),
+        }
+    }
+
+    /// the balance in the given currency, if the client has used it
+    pub fn balance_in(&self, currency : Currency) -> Option<&ClientBalance> {
+        if currency.is_default() {
+            return Some(&self.balance);
+        }
+        self.foreign.get(&currency)
+    }
+
+    /// the balance in the given currency, which is opened with the lock of
+    /// the account if the client has not used the currency before
+    pub(crate) fn balance_in_mut(&mut self, currency : Currency) -> &mut ClientBalance {
+        if currency.is_default() {
+            return &mut self.balance;
+        }
+
+        let locked = self.locked();
+        let client = self.balance.client();
+        self.foreign.entry(currency)
+            .or_insert_with(|| ClientBalance { locked, ..ClientBalance::new(client).with_currency(currency) })
+    }
+
+    /// the currency that a transaction is applied in, which for a dispute,
+    /// resolve, chargeback or release is that of the transaction it refers to
+    pub fn currency_of(&self, transaction : &Transaction) -> Currency {
+        match transaction.txn_type {
+            TransactionType::Dispute { .. } | TransactionType::Resolve { .. } | TransactionType::ChargeBack { .. } => {
+                self.get_record(transaction.tx).map(|record| record.currency).unwrap_or(transaction.currency)
+            },
+            TransactionType::Release => {
+                self.holds.get(&transaction.tx).map(|hold| hold.currency).unwrap_or(transaction.currency)
+            },
+            _ => transaction.currency,
+        }
+    }
+
+    /// a copy of the balance that the transaction is applied to, a new one
+    /// if the client has not used its currency yet
+    pub(crate) fn observed_balance(&self, transaction : &Transaction) -> ClientBalance {
+        let currency = self.currency_of(transaction);
+        match self.balance_in(currency) {
+            Some(balance) => balance.clone(),
+            None => ClientBalance { locked : self.locked(), ..ClientBalance::new(self.balance.client()).with_currency(currency) },
+        }
+    }
+
+    /// the balances of the client in each currency it has used. The default
+    /// currency is left out for a client that has only used other currencies
+    pub fn balances(&self) -> Balances<'_> {
+        let unused = !self.foreign.is_empty() && self.balance == ClientBalance { locked : self.balance.locked, ..ClientBalance::new(self.balance.client) };
+        Balances {
+            default : if unused { None } else { Some(&self.balance) },
+            foreign : self.foreign.values(),
+        }
+    }
+
+    /// an account is locked in all currencies at once
+    pub fn locked(&self) -> bool {
+        self.balance.locked() || self.foreign.values().any(ClientBalance::locked)
+    }
+
+    /// locks the balances of all currencies once any of them is locked
+    pub(crate) fn sync_lock(&mut self) {
+        if self.locked() {
+            self.set_locked(true);
         }
     }
 
+    fn set_locked(&mut self, locked : bool) {
+        for balance in std::iter::once(&mut self.balance).chain(self.foreign.values_mut()) {
+            balance.locked = locked;
+        }
+    }
+
+    /// all adjustments made by operators in the order they were made
+    pub fn adjustments(&self) -> &[AdjustmentEntry] {
+        &self.adjustments
+    }
+
+    /// all conversions of the client, in the order they were applied
+    pub fn conversions(&self) -> &[ConversionEntry] {
+        &self.conversions
+    }
+
+    /// all interest posted to the client, in the order it was posted
+    pub fn interest(&self) -> &[InterestEntry] {
+        &self.interest
+    }
+
+    /// interest accrued since it was last posted
+    pub fn accrued_interest(&self) -> impl Iterator<Item = (&Currency, &[InterestAccrual])> {
+        self.accruing.iter().map(|(currency, accruals)| (currency, accruals.as_slice()))
+    }
+
+    /// accrues interest on the available funds of each balance for the days
+    /// between `from` and `to`, whether or not the account is locked
+    pub(crate) fn accrue_interest(&mut self, schedule : &InterestSchedule, from : Timestamp, to : Timestamp) {
+        for balance in std::iter::once(&self.balance).chain(self.foreign.values()) {
+            let available = balance.available();
+            if *available <= 0.0 {
+                continue;
+            }
+            if let Some(rate) = schedule.rule.rate(available) {
+                let accrual = InterestAccrual::new(from, to, available, rate);
+                self.accruing.entry(balance.currency()).or_default().push(accrual);
+            }
+        }
+    }
+
+    /// credits the interest accrued in each currency to its balance
+    pub(crate) fn post_interest(&mut self, posted : Timestamp) {
+        for (currency, accruals) in std::mem::take(&mut self.accruing) {
+            let interest : f64 = accruals.iter().map(|accrual| accrual.interest).sum();
+            let amount = Amount::new(interest as f32).round_to(currency);
+            if *amount <= 0.0 {
+                continue;
+            }
+
+            self.balance_in_mut(currency).adjust(amount);
+            self.interest.push(InterestEntry { posted, currency, amount, accruals });
+        }
+    }
+
+    /// holds that have not been released or expired yet, in no particular order
+    pub fn holds(&self) -> impl Iterator<Item = &HoldEntry> {
+        self.holds.values()
+    }
+
+    /// releases a hold that has expired at `expires`, whether or not the
+    /// account is locked. A hold placed again with the same tx after the
+    /// expired one was released keeps its own expiry
+    pub(crate) fn expire_hold(&mut self, tx : TransactionId, expires : Timestamp) {
+        if self.holds.get(&tx).is_some_and(|hold| hold.expires == Some(expires)) {
+            if let Some(hold) = self.holds.remove(&tx) {
+                self.balance_in_mut(hold.currency).release(hold.amount);
+            }
+        }
+    }
+
+    /// all fees charged to the client in the order they were charged
+    pub fn fees(&self) -> &[FeeEntry] {
+        &self.fees
+    }
+
+    /// charges the fee, if any, for a transaction of the given amount. The
+    /// fee is capped at what the balance policy lets a dispute take from the
+    /// available amount, by default it is charged even if it goes below zero
+    pub(crate) fn charge_fee(&mut self, tx : TransactionId, source : FeeSource, fee : Option<&Fee>, amount : Amount,
+        currency : Currency, config : &EngineConfig)
+    {
+        let Some(fee) = fee else {
+            return;
+        };
+
+        let client = self.balance.client();
+        let balance = self.balance_in_mut(currency);
+
+        let mut fee_amount = fee.compute(amount).round_to(currency);
+        if let Some(floor) = config.balance_policy.floor(client, Debit::Imposed) {
+            let allowed = balance.available() - floor;
+            fee_amount = Amount::new(fee_amount.min(allowed.max(0.0)));
+        }
+
+        if fee_amount == 0.0 {
+            return;
+        }
+
+        balance.charge_fee(fee_amount);
+        self.fees.push(FeeEntry { tx, source, amount : fee_amount, currency });
+    }
+
     pub fn get_balance_mut(&mut self) -> &mut ClientBalance {
         &mut self.balance
     }
@@ -35,62 +372,281 @@ impl ClientLedger {
     pub fn record_transaction(&mut self, transaction : &Transaction) {
         match transaction.txn_type {
             TransactionType::Deposit { amount } => {
-                self.transactions.insert(transaction.tx, amount);
+                let record = TransactionRecord::new(RecordKind::Deposit, amount).with_currency(transaction.currency);
+                self.record(transaction.tx, record);
             },
             TransactionType::Withdrawal { amount } => {
-                self.transactions.insert(transaction.tx, amount);
+                let record = TransactionRecord::new(RecordKind::Withdrawal, amount).with_currency(transaction.currency);
+                self.record(transaction.tx, record);
             }
             _ => {
-                // nothing to record for any other type of transaction
+                // nothing to record for any other type of transaction, transfers
+                // are recorded on both sides when they are applied
             }
         }
     }
 
+    pub(crate) fn record(&mut self, id : TransactionId, record : TransactionRecord) {
+        self.transactions.insert(id, record);
+    }
+
     pub fn get_past_transaction(&self, id : TransactionId) -> Option<&Amount> {
+        self.transactions.get(&id).map(|record| &record.amount)
+    }
+
+    pub fn get_record(&self, id : TransactionId) -> Option<&TransactionRecord> {
         self.transactions.get(&id)
     }
 
+    /// the records of all past deposits, withdrawals and transfers, in no
+    /// particular order
+    pub fn records(&self) -> impl Iterator<Item = (TransactionId, &TransactionRecord)> {
+        self.transactions.iter().map(|(id, record)| (*id, record))
+    }
+
+    pub(crate) fn get_record_mut(&mut self, id : TransactionId) -> Option<&mut TransactionRecord> {
+        self.transactions.get_mut(&id)
+    }
+
+    /// fails with [`LedgerError::InsufficentFunds`] if taking the amount from
+    /// the available funds goes below what the balance policy allows
+    pub(crate) fn check_debit(&self, amount : Amount, currency : Currency, debit : Debit, config : &EngineConfig) -> crate::Result<()> {
+        let Some(floor) = config.balance_policy.floor(self.balance.client(), debit) else {
+            return Ok(());
+        };
+
+        let available = self.balance_in(currency).map(ClientBalance::available).unwrap_or(Amount::new(0.0));
+        if available < amount + floor {
+            return Err(LedgerError::InsufficentFunds { available : available - floor, requested : amount }.into());
+        }
+        Ok(())
+    }
+
+    /// fails with [`LedgerError::AccountLocked`] if the account is locked and
+    /// the lock policy does not allow the type of transaction
+    pub(crate) fn check_lock(&self, txn_type : &TransactionType, config : &EngineConfig) -> crate::Result<()> {
+        if self.locked() && !config.lock_policy.allows(txn_type) {
+            return Err(LedgerError::AccountLocked.into());
+        }
+        Ok(())
+    }
+
+    /// returns the record of a past deposit / withdrawal. Transfers can only
+    /// be disputed by their sender through the engine
+    fn disputable(&self, id : TransactionId) -> crate::Result<Option<TransactionRecord>> {
+        match self.transactions.get(&id) {
+            Some(TransactionRecord { kind : RecordKind::TransferOut { .. } | RecordKind::TransferIn { .. }, .. }) => {
+                Err(format!("Transfer {} can only be disputed by its sender", id).into())
+            },
+            Some(record) => Ok(Some(*record)),
+            None => Ok(None),
+        }
+    }
+
+    /// checks a withdrawal or transfer made at `at` against the withdrawal
+    /// limits of the client and forgets the withdrawals that have left its
+    /// window. Without limits nothing is remembered. An untimed withdrawal is
+    /// rejected when there are limits over a window, see `limits`
+    pub(crate) fn check_limits(&mut self, amount : Amount, currency : Currency, at : Option<Timestamp>, config : &EngineConfig) -> crate::Result<()> {
+        let Some(limits) = config.withdrawal_limits.for_client(self.balance.client()) else {
+            self.withdrawn.clear();
+            return Ok(());
+        };
+
+        // an untimed withdrawal has no window to add up in
+        let Some(at) = at else {
+            if limits.has_window() {
+                return Err(format!("Withdrawal without a timestamp cannot be limited over a {}", limits.window).into());
+            }
+            return Ok(limits.check_single(amount)?);
+        };
+
+        let start = limits.window.start(at);
+        self.withdrawn.retain(|(time, ..)| *time >= start);
+
+        let past = self.withdrawn.iter()
+            .filter(|(_, withdrawn_in, _)| *withdrawn_in == currency)
+            .map(|(_, _, amount)| *amount);
+        Ok(limits.check(amount, past)?)
+    }
+
+    /// remembers an amount that left the account at `at`, towards the
+    /// withdrawal limits
+    pub(crate) fn count_withdrawn(&mut self, amount : Amount, currency : Currency, at : Option<Timestamp>) {
+        if let Some(at) = at {
+            self.withdrawn.push((at, currency, amount));
+        }
+    }
+
     /// All transactions to the customer account are applied using `apply_transaction`
     /// 
-    pub fn apply_transaction(&mut self, transaction: &Transaction) -> crate::Result<()> {
+    /// 
+    /// The withdrawal and chargeback fees of the config are charged along with
+    /// the transaction. A withdrawal needs enough available for both the amount
+    /// and its fee. How far the available funds can go below zero is decided
+    /// by the balance policy of the config.
+    /// 
+    /// A transaction is applied to the balance in its currency, while a dispute,
+    /// resolve, chargeback or release is applied in the currency of the
+    /// transaction it refers to. A conversion uses the rate of the config that
+    /// is in effect at the timestamp of the transaction.
+    /// 
+    /// Lock, unlock and adjustment transactions are only accepted from an operator.
+    /// On a locked account only what the lock policy of the config allows is applied
+    pub fn apply_transaction(&mut self, transaction: &Transaction, config: &EngineConfig) -> crate::Result<()> {
+        if transaction.txn_type.is_administrative() && transaction.origin != Origin::Operator {
+            return Err(LedgerError::NotPermitted(transaction.txn_type.name()).into());
+        }
+        self.check_lock(&transaction.txn_type, config)?;
+
+        let currency = transaction.currency;
         match &transaction.txn_type {
             TransactionType::Deposit { amount } => {
-                self.balance.deposit(*amount)?;
+                self.balance_in_mut(currency).deposit(*amount)?;
             },
             TransactionType::Withdrawal { amount } => {
-                self.balance.withdrawal(*amount)?;
+                let fee = config.fees.withdrawal_in(currency);
+                let fee_amount = fee.map(|fee| fee.compute(*amount).round_to(currency)).unwrap_or(Amount::new(0.0));
+
+                let at = transaction.timestamp;
+                self.check_limits(*amount, currency, at, config)?;
+                self.check_debit(*amount + fee_amount, currency, Debit::Requested, config)?;
+                self.balance_in_mut(currency).debit(*amount);
+                self.charge_fee(transaction.tx, FeeSource::Withdrawal, fee, *amount, currency, config);
+                self.count_withdrawn(*amount, currency, at);
+            },
+            TransactionType::Fee { amount } => {
+                self.check_debit(*amount, currency, Debit::Requested, config)?;
+                self.balance_in_mut(currency).debit(*amount);
+                self.fees.push(FeeEntry { tx : transaction.tx, source : FeeSource::Fee, amount : *amount, currency });
             },
-            TransactionType::Dispute => {
+            TransactionType::Dispute { .. } => {
                 // If the tx specified by the dispute doesn't exist you can ignore it and 
                 // assume this is an error on our partners side.
-                if let Some(amount) = self.transactions.get(&transaction.tx) {
-                    self.balance.dispute(*amount)?;
+                if let Some(record) = self.disputable(transaction.tx)? {
+                    let amount = record.to_dispute(transaction)?;
+                    match (record.kind, config.withdrawal_disputes) {
+                        (RecordKind::Withdrawal, WithdrawalDisputes::Rejected) => {
+                            return Err(format!("Withdrawal {} cannot be disputed", transaction.tx).into());
+                        },
+                        (RecordKind::Withdrawal, WithdrawalDisputes::ProvisionalCredit) => {
+                            self.balance_in_mut(record.currency).credit_held(amount);
+                        },
+                        _ => {
+                            self.check_debit(amount, record.currency, Debit::Imposed, config)?;
+                            self.balance_in_mut(record.currency).dispute(amount)?;
+                        },
+                    }
+                    if let Some(record) = self.get_record_mut(transaction.tx) {
+                        record.dispute(amount);
+                    }
                 }
             },
-            TransactionType::Resolve => {
+            TransactionType::Resolve { .. } => {
                 // Funds that were previously disputed are no longer disputed. 
                 // This means that the clients held funds should decrease by the amount no longer disputed,
                 // their available funds should increase by the amount no longer disputed                
-                if let Some(amount) = self.transactions.get(&transaction.tx) {
-                    self.balance.resolve(*amount)?;
+                if let Some(record) = self.disputable(transaction.tx)? {
+                    let amount = record.to_release(transaction)?;
+                    let balance = self.balance_in_mut(record.currency);
+                    if is_provisional(&record, config) {
+                        // the withdrawal stands, so the provisional credit is taken back
+                        balance.remove_held(amount)?;
+                    } else {
+                        balance.resolve(amount)?;
+                    }
+                    if let Some(record) = self.get_record_mut(transaction.tx) {
+                        record.resolve(amount);
+                    }
                 }
             },
-            TransactionType::ChargeBack => {
+            TransactionType::ChargeBack { .. } => {
                 // A chargeback is the final state of a dispute and represents the client reversing a transaction. 
                 // Funds that were held have now been withdrawn. This means that the clients held funds and total funds 
                 // should decrease by the amount previously disputed.
-                if let Some(amount) = self.transactions.get(&transaction.tx) {
-                    self.balance.chargeback(*amount)?;
+                if let Some(record) = self.disputable(transaction.tx)? {
+                    let amount = record.to_release(transaction)?;
+                    let balance = self.balance_in_mut(record.currency);
+                    if is_provisional(&record, config) {
+                        // the withdrawal is reversed, the client gets the amount back
+                        balance.remove_held(amount)?;
+                        balance.chargeback_credit(amount)?;
+                    } else {
+                        balance.chargeback(amount)?;
+                    }
+                    self.charge_fee(transaction.tx, FeeSource::ChargeBack, config.fees.chargeback_in(record.currency), amount, record.currency, config);
+                    if let Some(record) = self.get_record_mut(transaction.tx) {
+                        record.charge_back(amount);
+                    }
                 }
             },
+            TransactionType::Transfer { .. } => {
+                return Err("A transfer involves two clients and has to be applied through the engine".into());
+            },
+            TransactionType::Lock => {
+                self.set_locked(true);
+            },
+            TransactionType::Unlock => {
+                self.set_locked(false);
+            },
+            TransactionType::Hold { amount, expires } => {
+                if self.holds.contains_key(&transaction.tx) {
+                    return Err(format!("Hold {} has already been placed", transaction.tx).into());
+                }
+
+                self.check_debit(*amount, currency, Debit::Requested, config)?;
+                self.balance_in_mut(currency).hold(*amount);
+                self.holds.insert(transaction.tx, HoldEntry { tx : transaction.tx, amount : *amount, currency, expires : *expires });
+            },
+            TransactionType::Release => {
+                // like disputes, a release of an unknown (or expired) hold is ignored
+                if let Some(hold) = self.holds.remove(&transaction.tx) {
+                    self.balance_in_mut(hold.currency).release(hold.amount);
+                }
+            },
+            TransactionType::Convert { amount, to } => {
+                let (rate, effective) = config.rates.rate(currency, *to, transaction.timestamp)
+                    .ok_or(format!("No rate from {} to {} for conversion {}", currency, to, transaction.tx))?;
+                let converted = rates::convert(*amount, rate, *to);
+
+                self.check_debit(*amount, currency, Debit::Requested, config)?;
+                self.balance_in_mut(currency).debit(*amount);
+                self.balance_in_mut(*to).deposit(converted)?;
+                self.conversions.push(ConversionEntry { tx : transaction.tx, from : currency, to : *to, amount : *amount, converted, rate, effective });
+            },
+            TransactionType::Adjustment { amount, reason } => {
+                self.balance_in_mut(currency).adjust(*amount);
+                self.adjustments.push(AdjustmentEntry { tx : transaction.tx, amount : *amount, currency, reason : reason.clone() });
+            },
         }
 
         self.record_transaction(transaction);
+        self.sync_lock();
 
         Ok(())
     }
 }
 
+/// `Balances` iterates over the balances of a client in each currency
+pub struct Balances<'a> {
+    default : Option<&'a ClientBalance>,
+    foreign : btree_map::Values<'a, Currency, ClientBalance>,
+}
+
+impl<'a> Iterator for Balances<'a> {
+    type Item = &'a ClientBalance;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.default.take().or_else(|| self.foreign.next())
+    }
+}
+
+/// whether a disputed record has been provisionally credited to held
+/// rather than moved from available
+fn is_provisional(record : &TransactionRecord, config : &EngineConfig) -> bool {
+    record.kind == RecordKind::Withdrawal && config.withdrawal_disputes == WithdrawalDisputes::ProvisionalCredit
+}
+
 /// `ClientBalance`
 ///
 /// Type [`ClientBalance`] holds the current balance of a client
@@ -98,14 +654,19 @@ impl ClientLedger {
 /// |Field|Description|
 /// |-|-|
 /// |client|The Id of the client|
+/// |currency|The currency of the balance, empty for the default currency|
 /// |available|Amount that is available for the client|
 /// |held|Amount that has been disputed|
 /// |locked|If a chargeback is transacted, the account is locked|
 /// |total()|Gives the total amount that is available for the client|
+///
+/// The balance does not check whether it is locked, the ledger decides that
+/// based on the lock policy of the config
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ClientBalance {
     client: ClientId,
+    currency : Currency,
     available : Amount,
     held : Amount,
     locked : bool
@@ -115,65 +676,96 @@ impl ClientBalance {
     pub fn new(client : ClientId) -> Self {
         ClientBalance {
             client: client,
+            currency : Currency::DEFAULT,
             available: Amount::new(0.0),
             held: Amount::new(0.0),
             locked: false,
         }
     }
 
+    pub fn with_currency(mut self, currency : Currency) -> Self {
+        self.currency = currency;
+        self
+    }
+
+    pub fn client(&self) -> ClientId {
+        self.client
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn available(&self) -> Amount {
+        self.available
+    }
+
+    pub fn held(&self) -> Amount {
+        self.held
+    }
+
+    pub fn locked(&self) -> bool {
+        self.locked
+    }
+
     pub fn total(&self) -> Amount {
         self.available + self.held
     }
 
     /// Deposits money to the client account
-    /// An Error [`Err(LedgerError::AccountLocked`] is returned in case the account is locked.
     pub fn deposit(&mut self, amount: Amount)  -> crate::Result<()> {
-        self.check_locked()?;
         self.available += amount;
         Ok(())
     }
 
     /// Withdraws money from the client account.
     /// 
-    /// An Error [`Err(LedgerError::AccountLocked`] is returned in case the account is locked.
     /// An Error [`Err(LedgerError::InsufficientFunds`] is returned in case the client does not have enough money to withdraw
     pub fn withdrawal(&mut self, amount: Amount) -> crate::Result<()> {
-        self.check_locked()?;
+        self.check_funds(amount)?;
+
+        self.available -= amount;
+        Ok(())
+    }
+
+    /// Takes the amount from the available without checking the funds, the
+    /// ledger checks them against the balance policy
+    pub fn debit(&mut self, amount: Amount) {
+        self.available -= amount;
+    }
+
+    /// Checks that the account has the requested amount available
+    pub fn check_funds(&self, amount: Amount) -> crate::Result<()> {
         if self.available < amount {
             return Err(LedgerError::InsufficentFunds { available: self.available, requested: amount }.into());
         }
 
-        self.available -= amount;
         Ok(())
     }
 
+    /// Charges a fee from the available amount. Fees are charged alongside
+    /// other transactions, which have already checked the account.
+    pub fn charge_fee(&mut self, amount: Amount) {
+        self.available -= amount;
+    }
+
     /// Disputes a given amount from the customer account
     /// 
     /// The requested amount is subtracted from the available and added to held
     /// 
-    /// An Error [`Err(LedgerError::AccountLocked`] is returned in case the account is locked.
+    /// Whether the available can go below zero is checked by the ledger
+    /// against the balance policy
     pub fn dispute(&mut self, amount: Amount) -> crate::Result<()> {
-        self.check_locked()?;
-
-        // don't know if this check is to be applied or not
-        // if self.available < amount {
-        //  return Err(LedgerError::InsufficentFunds { available: self.available, requested: amount }.into());
-        // }
-
         self.available -= amount;
         self.held += amount;
 
         Ok(())
     }
 
-    /// Disputes a given amount from the customer account
-    /// 
-    /// The requested amount is subtracted from the available and added to held
+    /// Resolves a dispute of the given amount
     /// 
-    /// An Error [`Err(LedgerError::AccountLocked`] is returned in case the account is locked.
+    /// The requested amount is subtracted from held and added to the available
     pub fn resolve(&mut self, amount: Amount) -> crate::Result<()> {
-        self.check_locked()?;
-
         if self.held < amount {
             return Err(format!("Insufficient amount {} held to resolve {}", self.held, amount).into());
         }
@@ -186,11 +778,7 @@ impl ClientBalance {
 
     /// Chargeback is a dispute resolution which causes the account to be locked.
     /// Amount from held is subtracted.
-    /// 
-    /// An Error [`Err(LedgerError::AccountLocked`] is returned in case the account is already locked.
     pub fn chargeback(&mut self, amount: Amount) -> crate::Result<()> {
-        self.check_locked()?;
-
         // not sure if this is to be done or not that the client cannot chargeback if the
         // held amount is < the amount
         // if self.held < amount {
@@ -203,35 +791,108 @@ impl ClientBalance {
         Ok(())
     }
 
-    fn check_locked(&self) -> crate::Result<()> {
-        if self.locked {
-            return Err(LedgerError::AccountLocked.into());
+    /// Holds the given amount, which is moved from the available to held
+    pub fn hold(&mut self, amount: Amount) {
+        self.available -= amount;
+        self.held += amount;
+    }
+
+    /// Releases a hold of the given amount back to the available
+    pub fn release(&mut self, amount: Amount) {
+        self.held -= amount;
+        self.available += amount;
+    }
+
+    /// Credits the given amount to held without touching the available, used
+    /// when a withdrawal is disputed with a provisional credit
+    pub fn credit_held(&mut self, amount: Amount) {
+        self.held += amount;
+    }
+
+    /// Removes a disputed amount from held without locking the account. Used
+    /// on the receiving side of a transfer that has been charged back and
+    /// for provisional credits.
+    pub fn remove_held(&mut self, amount: Amount) -> crate::Result<()> {
+        if self.held < amount {
+            return Err(format!("Insufficient amount {} held to charge back {}", self.held, amount).into());
         }
+
+        self.held -= amount;
         Ok(())
     }
+
+    /// Credits back an amount that has been charged back and locks the
+    /// account. Used on the sending side of a transfer.
+    pub fn chargeback_credit(&mut self, amount: Amount) -> crate::Result<()> {
+        self.available += amount;
+        self.locked = true;
+
+        Ok(())
+    }
+
+    /// Locks the account, only done by an operator
+    pub fn lock(&mut self) {
+        self.locked = true;
+    }
+
+    /// Unlocks the account, only done by an operator
+    pub fn unlock(&mut self) {
+        self.locked = false;
+    }
+
+    /// Corrects the available amount by a positive or negative amount. This
+    /// is done by an operator, so it is applied even if the account is locked
+    pub fn adjust(&mut self, amount: Amount) {
+        self.available += amount;
+    }
 }
 
 /// Serializer trait for ClientBalance
 /// 
 /// A custom serializer has been written instead of deriving from Serializer because 
-/// the total field is method and not a member field. But we want to write that out as well
+/// the total field is method and not a member field. But we want to write that out as well.
+/// Amounts are written with the minor units of the currency
 impl Serialize for ClientBalance {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        let mut state = serializer.serialize_struct("ClientBalance", 5)?;
+        let mut state = serializer.serialize_struct("ClientBalance", 6)?;
 
         state.serialize_field("client", &self.client)?;
-        state.serialize_field("available", &self.available)?;
-        state.serialize_field("held", &self.held)?;
-        state.serialize_field("total", &self.total())?;
+        state.serialize_field("currency", &self.currency)?;
+        state.serialize_field("available", &self.available.display_in(self.currency))?;
+        state.serialize_field("held", &self.held.display_in(self.currency))?;
+        state.serialize_field("total", &self.total().display_in(self.currency))?;
         state.serialize_field("locked", &self.locked)?;
 
         state.end()
     }
 }
 
+/// `WithoutCurrency` writes a balance in the default currency with the
+/// columns of the original output, `client,available,held,total,locked`, for
+/// output that has no balances in other currencies
+pub struct WithoutCurrency<'a>(pub &'a ClientBalance);
+
+impl Serialize for WithoutCurrency<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let balance = self.0;
+        let mut state = serializer.serialize_struct("ClientBalance", 5)?;
+
+        state.serialize_field("client", &balance.client)?;
+        state.serialize_field("available", &balance.available.display_in(balance.currency))?;
+        state.serialize_field("held", &balance.held.display_in(balance.currency))?;
+        state.serialize_field("total", &balance.total().display_in(balance.currency))?;
+        state.serialize_field("locked", &balance.locked)?;
+
+        state.end()
+    }
+}
+
 // todo: write a Deserializer for ClientBalance
 
 /// `LedgerError` represents all errors that might occur in
@@ -240,7 +901,28 @@ impl Serialize for ClientBalance {
 pub enum LedgerError {
     InsufficentFunds { available: Amount, requested: Amount },
     CustomerMissing(ClientId),
-    AccountLocked
+    AccountLocked,
+    /// an administrative transaction that did not come from an operator
+    NotPermitted(&'static str),
+    /// a withdrawal over one of the client's withdrawal limits, along with
+    /// the window of the limit if it has one
+    LimitExceeded { limit: Limit, window: Option<LimitWindow> },
+    /// rejected by the screening, along with why
+    Rejected(String),
+}
+
+impl LedgerError {
+    /// a short name for the kind of error, e.g. for counting errors by kind
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LedgerError::InsufficentFunds { .. } => "insufficient_funds",
+            LedgerError::CustomerMissing(_) => "customer_missing",
+            LedgerError::AccountLocked => "account_locked",
+            LedgerError::NotPermitted(_) => "not_permitted",
+            LedgerError::LimitExceeded { .. } => "limit_exceeded",
+            LedgerError::Rejected(_) => "rejected",
+        }
+    }
 }
 
 impl fmt::Display for LedgerError {
@@ -255,6 +937,18 @@ impl fmt::Display for LedgerError {
             LedgerError::AccountLocked => {
                 write!(f, "Customer account is locked and transaction cannot be carried out")
             },
+            LedgerError::NotPermitted(txn_type) => {
+                write!(f, "Only an operator can apply a {} transaction", txn_type)
+            },
+            LedgerError::LimitExceeded { limit, window : Some(window) } => {
+                write!(f, "Withdrawal exceeds the limit of {} per {}", limit, window)
+            },
+            LedgerError::LimitExceeded { limit, window : None } => {
+                write!(f, "Withdrawal exceeds the limit of {}", limit)
+            },
+            LedgerError::Rejected(reason) => {
+                write!(f, "Transaction rejected by screening, {}", reason)
+            },
         }
     }
 }
@@ -0,0 +1,200 @@
+//! Withdrawal limits.
+//!
+//! `WithdrawalLimits` caps the withdrawals of each client, with a default
+//! for clients without limits of their own. `Limits` can cap a single
+//! withdrawal, the total withdrawn and the number of withdrawals within a
+//! window, which is either the calendar day (UTC) or a rolling number of
+//! seconds. Withdrawals are timed by their timestamp, or by the engine's
+//! clock when they have none, and each currency is limited on its own. A
+//! withdrawal that is still untimed, as neither it nor the engine has a
+//! time, cannot be placed in a window, so it is rejected when the client has
+//! a total or count limit and only checked against the single withdrawal
+//! limit otherwise.
+//!
+//! Transfers to another client move money out just like a withdrawal, so
+//! they count towards the same limits. Fees are charged by the engine rather
+//! than asked for by the client and conversions keep the money in the
+//! account, so neither is limited.
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::Deserialize;
+
+use super::{ClientId, Timestamp};
+use super::amount::Amount;
+use super::interest::DAY;
+use super::ledger::LedgerError;
+
+/// `LimitWindow` is the time over which withdrawals add up towards a limit
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LimitWindow {
+    /// the calendar day of the withdrawal
+    #[default]
+    Daily,
+    /// the given number of seconds up to the withdrawal
+    Rolling(Timestamp),
+}
+
+impl LimitWindow {
+    /// the earliest time in the window of a withdrawal made at `at`
+    pub fn start(&self, at : Timestamp) -> Timestamp {
+        match self {
+            LimitWindow::Daily => at - at % DAY,
+            LimitWindow::Rolling(seconds) => (at + 1).saturating_sub(*seconds),
+        }
+    }
+
+    /// `parse` reads `daily` or a number of seconds
+    pub fn parse(window : &str) -> crate::Result<Self> {
+        match window {
+            "daily" => Ok(LimitWindow::Daily),
+            seconds => match seconds.parse::<Timestamp>() {
+                Ok(seconds) if seconds > 0 => Ok(LimitWindow::Rolling(seconds)),
+                _ => Err(format!("Unknown limit window {}", window).into()),
+            },
+        }
+    }
+}
+
+impl fmt::Display for LimitWindow {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitWindow::Daily => write!(f, "day"),
+            LimitWindow::Rolling(seconds) => write!(f, "{} seconds", seconds),
+        }
+    }
+}
+
+/// `Limit` is the limit that a withdrawal would go over
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limit {
+    Single(Amount),
+    Total(Amount),
+    Count(u32),
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::Single(amount) => write!(f, "{} for a single withdrawal", amount),
+            Limit::Total(amount) => write!(f, "{} withdrawn", amount),
+            Limit::Count(count) => write!(f, "{} withdrawals", count),
+        }
+    }
+}
+
+/// `Limits` caps the withdrawals of a client
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Limits {
+    pub max_single : Option<Amount>,
+    pub max_total : Option<Amount>,
+    pub max_count : Option<u32>,
+    pub window : LimitWindow,
+}
+
+impl Limits {
+    /// checks a withdrawal of `amount` against the limits, given the amounts
+    /// withdrawn earlier in its window
+    pub fn check(&self, amount : Amount, past : impl Iterator<Item = Amount>) -> Result<(), LedgerError> {
+        self.check_single(amount)?;
+
+        let (count, total) = past.fold((0u32, Amount::new(0.0)), |(count, total), amount| (count + 1, total + amount));
+        if let Some(max) = self.max_count.filter(|max| count >= *max) {
+            return Err(LedgerError::LimitExceeded { limit : Limit::Count(max), window : Some(self.window) });
+        }
+        if let Some(max) = self.max_total.filter(|max| total + amount > *max) {
+            return Err(LedgerError::LimitExceeded { limit : Limit::Total(max), window : Some(self.window) });
+        }
+
+        Ok(())
+    }
+
+    /// whether there are limits over a window, which need a withdrawal to be
+    /// timed
+    pub fn has_window(&self) -> bool {
+        self.max_total.is_some() || self.max_count.is_some()
+    }
+
+    /// checks a withdrawal of `amount` against the single withdrawal limit only
+    pub fn check_single(&self, amount : Amount) -> Result<(), LedgerError> {
+        match self.max_single.filter(|max| amount > *max) {
+            Some(max) => Err(LedgerError::LimitExceeded { limit : Limit::Single(max), window : None }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// `WithdrawalLimits` holds the limits of each client. By default there
+/// are no limits
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WithdrawalLimits {
+    default : Option<Limits>,
+    clients : HashMap<ClientId, Limits>,
+}
+
+/// a single line of a withdrawal limits file
+#[derive(Debug, Deserialize)]
+struct LimitsRecord {
+    client : Option<ClientId>,
+    max_single : Option<f32>,
+    max_total : Option<f32>,
+    max_count : Option<u32>,
+    window : Option<String>,
+}
+
+impl WithdrawalLimits {
+    /// the limits of clients that do not have their own
+    pub fn with_default(mut self, limits : Limits) -> Self {
+        self.default = Some(limits);
+        self
+    }
+
+    pub fn with_client(mut self, client : ClientId, limits : Limits) -> Self {
+        self.clients.insert(client, limits);
+        self
+    }
+
+    /// the limits that apply to the client, if any
+    pub fn for_client(&self, client : ClientId) -> Option<&Limits> {
+        self.clients.get(&client).or(self.default.as_ref())
+    }
+
+    /// `from_csv` loads the limits from a csv file with the columns
+    /// `client,max_single,max_total,max_count,window`, e.g.
+    ///
+    /// ```text
+    /// client,max_single,max_total,max_count,window
+    /// ,1000,5000,10,daily
+    /// 7,250,,3,3600
+    /// ```
+    ///
+    /// A line without a client is the default. `window` is `daily` or a
+    /// number of seconds, daily when not given
+    pub fn from_csv(path : &str) -> crate::Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_path(path)?;
+
+        let mut limits = WithdrawalLimits::default();
+        for record in reader.deserialize::<LimitsRecord>() {
+            let record = record?;
+            let window = match record.window.as_deref() {
+                Some(window) => LimitWindow::parse(window)?,
+                None => LimitWindow::Daily,
+            };
+            let client_limits = Limits {
+                max_single : record.max_single.map(Amount::new),
+                max_total : record.max_total.map(Amount::new),
+                max_count : record.max_count,
+                window,
+            };
+
+            limits = match record.client {
+                Some(client) => limits.with_client(client, client_limits),
+                None => limits.with_default(client_limits),
+            };
+        }
+
+        Ok(limits)
+    }
+}
@@ -3,23 +3,62 @@
 //! Applies given transcations to customer accounts. A ledger is maintained
 //! for each customer, which keeps a track of all transactions that have
 //! been applied to the account and the current balance
-use std::collections::HashMap;
+//!
+//! The engine keeps a clock that follows the timestamps of the transactions
+//! it applies. Holds with an expiry are released once the clock reaches it,
+//! and interest accrues for each day the clock moves past.
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
 use amount::Amount;
 use core::str::FromStr;
 use std::fmt::Debug;
-use serde::de::{self, Deserializer, Visitor, MapAccess};
+use serde::de::{self, Deserializer, DeserializeSeed, Visitor, MapAccess};
 use serde::{Deserialize};
-use ledger::{ClientBalance, ClientLedger};
+use ledger::{Balances, ClientBalance, ClientLedger, RecordKind};
+use config::EngineConfig;
+use currency::Currency;
+use interest::DAY;
+use ledger::LedgerError;
+use screening::{Flagged, Screening, Verdict};
+use observer::EngineObserver;
+use verify::Violation;
+use batch::{BatchError, UndoLog};
 
 pub type ClientId = u16;
 pub type TransactionId = u32;
+pub type Timestamp = u64;
 
 pub mod ledger;
 pub mod amount;
+pub mod sharded;
+pub mod config;
+pub mod fees;
+pub mod interest;
+pub mod limits;
+pub mod currency;
+pub mod rates;
+pub mod screening;
+pub mod observer;
+pub mod verify;
+pub mod batch;
+mod transfer;
 
 #[derive(Debug)]
 pub struct TransactionEngine {
-    ledger: HashMap<ClientId, ClientLedger>
+    ledger: HashMap<ClientId, ClientLedger>,
+    config: EngineConfig,
+    clock: Option<Timestamp>,
+    /// holds by when they expire, holds that have been released before are
+    /// skipped when they come up
+    expiries: BinaryHeap<Reverse<(Timestamp, ClientId, TransactionId)>>,
+    /// start of the day up to which interest has accrued
+    accrued_to: Option<Timestamp>,
+    screening: Option<Box<dyn Screening>>,
+    flagged: Vec<Flagged>,
+    observers: Vec<Box<dyn EngineObserver>>,
+    /// what the batch being applied has changed, if any
+    undo: Option<UndoLog>,
 }
 
 /// `TransactionEngine` is used for keeping all customer accounts
@@ -28,18 +67,269 @@ pub struct TransactionEngine {
 /// It provides an `iter` function to iterate over all client accounts
 impl TransactionEngine {
     pub fn new() -> Self {
+        Self::with_config(EngineConfig::default())
+    }
+
+    pub fn with_config(config : EngineConfig) -> Self {
         TransactionEngine {
-            ledger : HashMap::new()
+            ledger : HashMap::new(),
+            config,
+            clock : None,
+            expiries : BinaryHeap::new(),
+            accrued_to : None,
+            screening : None,
+            flagged : Vec::new(),
+            observers : Vec::new(),
+            undo : None,
         }
     }
 
+    /// registers an observer that is told about every transaction applied
+    pub fn with_observer(mut self, observer : Box<dyn EngineObserver>) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    /// sets the screening that every transaction goes through before it is applied
+    pub fn with_screening(mut self, screening : Box<dyn Screening>) -> Self {
+        self.screening = Some(screening);
+        self
+    }
+
+    /// transactions flagged by the screening that were applied, in the order
+    /// they were applied
+    pub fn flagged(&self) -> &[Flagged] {
+        &self.flagged
+    }
+
+    pub fn config(&self) -> &EngineConfig {
+        &self.config
+    }
+
     /// Given a transaction it applies it to the given client
     /// In case a client account is not found, a new one is created
     /// 
+    /// A transaction is screened before anything else, then a transaction
+    /// with a timestamp advances the clock to it. The screening is told about
+    /// the transaction once it has been applied.
+    /// 
     /// Certain errors can be returned from this, e.g.
     ///     LedgerError::InsufficentFund
     ///     LedgerError::AccountLocked
-    pub fn apply(&mut self, transaction : Transaction) -> crate::Result<()> {
+    ///     LedgerError::LimitExceeded
+    ///     LedgerError::Rejected
+    pub fn apply(&mut self, mut transaction : Transaction) -> crate::Result<()> {
+        let before = self.observed_balance(&transaction);
+        let received = self.observed_counterparty(&transaction);
+        let result = self.apply_screened(&mut transaction);
+        self.notify(&transaction, before, &result);
+        if result.is_ok() {
+            self.notify_counterparty(&transaction, received);
+        }
+        result
+    }
+
+    /// `apply_batch` applies the transactions in order, all or nothing. When
+    /// one fails the engine is rolled back to the state it was in before the
+    /// batch, see `batch`, and a [`BatchError`] is returned with the error of
+    /// the transaction.
+    ///
+    /// Observers are only told about the transactions once the whole batch
+    /// has been applied. A batch that is rolled back is only told as the
+    /// rejection of the transaction that failed
+    pub fn apply_batch(&mut self, transactions : impl IntoIterator<Item = Transaction>) -> crate::Result<()> {
+        self.undo = Some(UndoLog::new(self));
+
+        let mut applied = Vec::new();
+        for (index, mut transaction) in transactions.into_iter().enumerate() {
+            let before = self.observed_balance(&transaction);
+            let received = self.observed_counterparty(&transaction);
+            let result = self.apply_screened(&mut transaction);
+            if let Err(error) = result {
+                if let Some(undo) = self.undo.take() {
+                    undo.roll_back(self);
+                }
+
+                let result = Err(error);
+                self.notify(&transaction, before, &result);
+                return result.map_err(|error| BatchError { index, tx : transaction.tx, error }.into());
+            }
+
+            let after = self.observed_balance(&transaction);
+            let received = received.map(|(client, before)| (before, self.counterparty_balance(client, &transaction)));
+            applied.push((transaction, before, after, received));
+        }
+        self.undo = None;
+
+        for (transaction, before, after, received) in applied {
+            if let (Some(before), Some(after)) = (before, after) {
+                self.notify_applied(&transaction, &before, &after);
+            }
+            if let Some((before, after)) = received {
+                self.notify_counterparty_applied(&transaction, &before, &after);
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_screened(&mut self, transaction : &mut Transaction) -> crate::Result<()> {
+        let flag = self.screen(transaction)?;
+        if let Some(timestamp) = transaction.timestamp {
+            self.advance_to(timestamp);
+        }
+
+        self.time(transaction);
+
+        let expiry = match transaction.txn_type {
+            TransactionType::Hold { expires : Some(expires), .. } => {
+                if self.clock.is_some_and(|now| expires <= now) {
+                    return Err(format!("Hold {} has already expired", transaction.tx).into());
+                }
+                Some((expires, transaction.client, transaction.tx))
+            },
+            _ => None,
+        };
+
+        self.apply_now(transaction)?;
+
+        if let Some(expiry) = expiry {
+            self.expiries.push(Reverse(expiry));
+            if let Some(undo) = &mut self.undo {
+                undo.scheduled(expiry);
+            }
+        }
+        self.screened(transaction, flag);
+        Ok(())
+    }
+
+    /// a conversion without a timestamp uses the rate in effect at the clock,
+    /// and a withdrawal or transfer counts towards its limits at the clock
+    fn time(&self, transaction : &mut Transaction) {
+        if let TransactionType::Convert { .. } | TransactionType::Withdrawal { .. } | TransactionType::Transfer { .. } = transaction.txn_type {
+            transaction.timestamp = transaction.timestamp.or(self.clock);
+        }
+    }
+
+    /// runs the screening, if any, on a transaction that is about to be
+    /// applied and returns why it is flagged, if it is
+    fn screen(&self, transaction : &Transaction) -> crate::Result<Option<String>> {
+        let Some(screening) = self.screening.as_ref() else {
+            return Ok(None);
+        };
+
+        let now = transaction.timestamp.or(self.clock);
+        match screening.screen(transaction, self.ledger.get(&transaction.client), now) {
+            Verdict::Allow => Ok(None),
+            Verdict::Flag(reason) => Ok(Some(reason)),
+            Verdict::Reject(reason) => Err(LedgerError::Rejected(reason).into()),
+        }
+    }
+
+    /// tells the screening about a transaction that has been applied, and
+    /// keeps it as flagged when it was
+    fn screened(&mut self, transaction : &Transaction, flag : Option<String>) {
+        let now = transaction.timestamp.or(self.clock);
+        if let Some(screening) = self.screening.as_mut() {
+            screening.applied(transaction, self.ledger.get(&transaction.client), now);
+        }
+
+        if let Some(reason) = flag {
+            self.flagged.push(Flagged {
+                client : transaction.client,
+                tx : transaction.tx,
+                txn_type : transaction.txn_type.name(),
+                reason,
+            });
+        }
+    }
+
+    /// the balance of the client that the transaction is applied to, only
+    /// taken when there are observers
+    fn observed_balance(&self, transaction : &Transaction) -> Option<ClientBalance> {
+        if self.observers.is_empty() {
+            return None;
+        }
+
+        match self.ledger.get(&transaction.client) {
+            Some(ledger) => Some(ledger.observed_balance(transaction)),
+            None => Some(ClientBalance::new(transaction.client).with_currency(transaction.currency)),
+        }
+    }
+
+    /// the balance of the counterparty of the transaction, e.g. the receiver
+    /// of a transfer, only taken when there are observers
+    fn observed_counterparty(&self, transaction : &Transaction) -> Option<(ClientId, ClientBalance)> {
+        if self.observers.is_empty() {
+            return None;
+        }
+
+        let client = self.counterparty(transaction)?;
+        Some((client, self.counterparty_balance(client, transaction)))
+    }
+
+    fn counterparty_balance(&self, client : ClientId, transaction : &Transaction) -> ClientBalance {
+        match self.ledger.get(&client) {
+            Some(ledger) => ledger.observed_balance(transaction),
+            None => ClientBalance::new(client).with_currency(transaction.currency),
+        }
+    }
+
+    /// tells the observers about a transaction that has been applied to its
+    /// counterparty
+    fn notify_counterparty(&mut self, transaction : &Transaction, received : Option<(ClientId, ClientBalance)>) {
+        let Some((client, before)) = received else {
+            return;
+        };
+
+        let after = self.counterparty_balance(client, transaction);
+        self.notify_counterparty_applied(transaction, &before, &after);
+    }
+
+    fn notify_counterparty_applied(&mut self, transaction : &Transaction, before : &ClientBalance, after : &ClientBalance) {
+        for observer in self.observers.iter_mut() {
+            observer.on_counterparty_applied(transaction, before, after);
+        }
+    }
+
+    /// tells the observers about a transaction given to `apply`
+    fn notify(&mut self, transaction : &Transaction, before : Option<ClientBalance>, result : &crate::Result<()>) {
+        let Some(before) = before else {
+            return;
+        };
+
+        let after = match result {
+            Ok(_) => self.observed_balance(transaction).unwrap_or_else(|| before.clone()),
+            Err(e) => {
+                for observer in self.observers.iter_mut() {
+                    observer.on_rejected(transaction, e);
+                }
+                return;
+            },
+        };
+        self.notify_applied(transaction, &before, &after);
+    }
+
+    /// tells the observers about a transaction that has been applied
+    fn notify_applied(&mut self, transaction : &Transaction, before : &ClientBalance, after : &ClientBalance) {
+        for observer in self.observers.iter_mut() {
+            observer.on_applied(transaction, before, after);
+            if let TransactionType::Dispute { .. } = transaction.txn_type {
+                observer.on_dispute_opened(transaction, before, after);
+            }
+            if after.locked() && !before.locked() {
+                observer.on_locked(transaction, after);
+            }
+        }
+    }
+
+    fn apply_now(&mut self, transaction : &Transaction) -> crate::Result<()> {
+        if let Some(counterparty) = self.counterparty(transaction) {
+            return self.apply_between(counterparty, transaction);
+        }
+
+        if let Some(undo) = &mut self.undo {
+            undo.save(&self.ledger, transaction.client);
+        }
         let mut client_ledger = self.ledger.get_mut(&transaction.client);
 
         if let None = client_ledger {
@@ -50,47 +340,294 @@ impl TransactionEngine {
         }
 
         let ledger = client_ledger.ok_or("Customer ledger not found")?;
-        ledger.apply_transaction(&transaction)
+        ledger.apply_transaction(transaction, &self.config)
+    }
+
+    /// the latest timestamp the engine has seen, if any
+    pub fn clock(&self) -> Option<Timestamp> {
+        self.clock
     }
 
-    /// Provides an itereator over all customer accounts. There is no
-    /// particular order in which the accounts are returned
+    /// `advance_to` moves the clock forward, accrues and posts interest for
+    /// the days that have passed and releases all holds that expire by then.
+    /// The clock never goes back
+    pub fn advance_to(&mut self, now : Timestamp) {
+        if self.clock.is_some_and(|clock| clock >= now) {
+            return;
+        }
+        self.clock = Some(now);
+        self.accrue_to(now);
+
+        while let Some(Reverse((expires, client, tx))) = self.expiries.peek().copied() {
+            if expires > now {
+                break;
+            }
+            self.expiries.pop();
+            if let Some(undo) = &mut self.undo {
+                undo.expired((expires, client, tx));
+                undo.save(&self.ledger, client);
+            }
+
+            if let Some(ledger) = self.ledger.get_mut(&client) {
+                ledger.expire_hold(tx, expires);
+            }
+        }
+    }
+
+    /// accrues interest on all balances for each whole day up to `now`, and
+    /// posts it at the end of every period on the way. Interest starts to
+    /// accrue from the first day the clock is set
+    fn accrue_to(&mut self, now : Timestamp) {
+        let Some(schedule) = &self.config.interest else {
+            return;
+        };
+
+        let today = now - now % DAY;
+        let mut day = *self.accrued_to.get_or_insert(today);
+        while day < today {
+            let period_end = schedule.period_end(day);
+            let until = period_end.min(today);
+            if let Some(undo) = &mut self.undo {
+                for client in self.ledger.keys() {
+                    undo.save(&self.ledger, *client);
+                }
+            }
+            for ledger in self.ledger.values_mut() {
+                ledger.accrue_interest(schedule, day, until);
+                if until == period_end {
+                    ledger.post_interest(period_end);
+                }
+            }
+            day = until;
+        }
+        self.accrued_to = Some(today);
+    }
+
+    /// `counterparty` returns the other client involved in a transfer, or in
+    /// a dispute / resolve / chargeback of a transfer made by the client
+    pub fn counterparty(&self, transaction : &Transaction) -> Option<ClientId> {
+        match transaction.txn_type {
+            TransactionType::Transfer { to_client, .. } => Some(to_client),
+            TransactionType::Dispute { .. } | TransactionType::Resolve { .. } | TransactionType::ChargeBack { .. } => {
+                let record = self.ledger.get(&transaction.client)?.get_record(transaction.tx)?;
+                match record.kind {
+                    RecordKind::TransferOut { to } => Some(to),
+                    _ => None,
+                }
+            },
+            _ => None,
+        }
+    }
+
+    /// applies a transaction that involves two clients of this engine
+    fn apply_between(&mut self, counterparty : ClientId, transaction : &Transaction) -> crate::Result<()> {
+        if counterparty == transaction.client {
+            return Err(format!("Client {} cannot transfer to itself", counterparty).into());
+        }
+        if let Some(undo) = &mut self.undo {
+            undo.save(&self.ledger, transaction.client);
+            undo.save(&self.ledger, counterparty);
+        }
+
+        // a receiver that only the failed transaction created is removed again
+        let new_receiver = !self.ledger.contains_key(&counterparty);
+        self.ledger.entry(transaction.client).or_insert_with(|| ClientLedger::new(transaction.client));
+        self.ledger.entry(counterparty).or_insert_with(|| ClientLedger::new(counterparty));
+
+        let result = match self.ledger.get_disjoint_mut([&transaction.client, &counterparty]) {
+            [Some(sender), Some(receiver)] => transfer::apply(sender, receiver, transaction, &self.config),
+            _ => Err("Customer ledger not found".into()),
+        };
+        if result.is_err() && new_receiver {
+            self.ledger.remove(&counterparty);
+        }
+        result
+    }
+
+    /// `apply_across` applies a transaction whose client belongs to this engine
+    /// but whose counterparty belongs to another engine, e.g. a transfer
+    /// between clients of two different shards
+    pub(crate) fn apply_across(&mut self, other : &mut TransactionEngine, mut transaction : Transaction) -> crate::Result<()> {
+        self.time(&mut transaction);
+        let before = self.observed_balance(&transaction);
+        let received = self.counterparty(&transaction)
+            .filter(|_| !other.observers.is_empty())
+            .map(|client| (client, other.counterparty_balance(client, &transaction)));
+        let result = self.apply_across_screened(other, &transaction);
+        self.notify(&transaction, before, &result);
+        if result.is_ok() {
+            other.notify_counterparty(&transaction, received);
+        }
+        result
+    }
+
+    fn apply_across_screened(&mut self, other : &mut TransactionEngine, transaction : &Transaction) -> crate::Result<()> {
+        let flag = self.screen(transaction)?;
+        let counterparty = self.counterparty(transaction)
+            .ok_or(format!("Transaction {} does not involve another client", transaction.tx))?;
+
+        let new_receiver = !other.ledger.contains_key(&counterparty);
+        let sender = self.ledger.entry(transaction.client)
+            .or_insert_with(|| ClientLedger::new(transaction.client));
+        let receiver = other.ledger.entry(counterparty)
+            .or_insert_with(|| ClientLedger::new(counterparty));
+
+        if let Err(e) = transfer::apply(sender, receiver, transaction, &self.config) {
+            if new_receiver {
+                other.ledger.remove(&counterparty);
+            }
+            return Err(e);
+        }
+        self.screened(transaction, flag);
+        Ok(())
+    }
+
+    /// Provides an itereator over all customer accounts, one balance for
+    /// each currency of a client. There is no particular order in which the
+    /// accounts are returned
     pub fn iter(&self) -> ClientIterator<'_> {
         ClientIterator {
-            iter : self.ledger.iter()
+            iter : self.ledger.iter(),
+            balances : None,
         }
     }
 
+    /// Provides an iterator over the ledgers of all customers, in no
+    /// particular order
+    pub fn ledgers(&self) -> impl Iterator<Item = &ClientLedger> {
+        self.ledger.values()
+    }
+
     /// `get_ledger` can be used to get the ledger of a particular client
     pub fn get_ledger(&self, client : ClientId) -> Option<&ClientLedger> {
         self.ledger.get(&client)
     }
+
+    /// `verify` checks the invariants of every client's balances against its
+    /// ledger, see `verify::verify`. The violations are ordered by client
+    pub fn verify(&self) -> Vec<Violation> {
+        let mut clients : Vec<&ClientId> = self.ledger.keys().collect();
+        clients.sort();
+
+        clients.into_iter()
+            .flat_map(|client| verify::verify(&self.ledger[client], &self.config))
+            .collect()
+    }
+
+    /// `find_transaction` looks up a past deposit / withdrawal across all
+    /// clients and returns the client it belongs to along with its amount.
+    /// A transfer is returned along with its sender
+    pub fn find_transaction(&self, tx : TransactionId) -> Option<(ClientId, Amount)> {
+        self.ledger.iter()
+            .find_map(|(client, ledger)| {
+                let record = ledger.get_record(tx)?;
+                match record.kind {
+                    RecordKind::TransferIn { .. } => None,
+                    _ => Some((*client, record.amount)),
+                }
+            })
+    }
+
+    /// `absorb` moves all client ledgers of another engine into this one.
+    /// The engines are expected to hold disjoint sets of clients
+    pub(crate) fn absorb(&mut self, other : TransactionEngine) {
+        self.ledger.extend(other.ledger);
+        self.expiries.extend(other.expiries);
+        self.clock = self.clock.max(other.clock);
+        self.accrued_to = self.accrued_to.max(other.accrued_to);
+        self.flagged.extend(other.flagged);
+    }
 }
 
 
 pub struct ClientIterator<'a> {
-    iter : std::collections::hash_map::Iter<'a, ClientId, ClientLedger>
+    iter : std::collections::hash_map::Iter<'a, ClientId, ClientLedger>,
+    balances : Option<Balances<'a>>,
 }
 
 impl<'a> Iterator for ClientIterator<'a> {
     type Item = &'a ClientBalance;
     
     fn next(&mut self) -> Option<Self::Item> { 
-        let (_, client_ledger) = &self.iter.next()?;
-        Some(&client_ledger.get_balance())
+        loop {
+            if let Some(balance) = self.balances.as_mut().and_then(Iterator::next) {
+                return Some(balance);
+            }
+            let (_, client_ledger) = self.iter.next()?;
+            self.balances = Some(client_ledger.balances());
+        }
     }
 }
 
 
 /// `TransactionType` represents the particular transactions that
 /// can be applied to an ClientAccount
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum TransactionType {
     Deposit { amount: Amount },
     Withdrawal { amount: Amount },
-    Dispute, 
-    Resolve,
-    ChargeBack,
+    /// disputes `amount` of a past transaction, all of what is not yet
+    /// disputed when no amount is given. The same goes for resolve and chargeback
+    Dispute { amount: Option<Amount> },
+    Resolve { amount: Option<Amount> },
+    ChargeBack { amount: Option<Amount> },
+    Transfer { to_client: ClientId, amount: Amount },
+    Fee { amount: Amount },
+    Lock,
+    Unlock,
+    /// a correction of the available amount, which can be negative
+    Adjustment { amount: Amount, reason: String },
+    /// holds `amount` of the available funds, e.g. for a pending card
+    /// authorisation, until it is released or expires
+    Hold { amount: Amount, expires: Option<Timestamp> },
+    /// releases the hold placed by the transaction of the same tx
+    Release,
+    /// converts `amount` of the transaction's currency to the `to` currency
+    Convert { amount: Amount, to: Currency },
+}
+
+impl TransactionType {
+    /// the name used for the type in the input
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransactionType::Deposit { .. } => "deposit",
+            TransactionType::Withdrawal { .. } => "withdrawal",
+            TransactionType::Dispute { .. } => "dispute",
+            TransactionType::Resolve { .. } => "resolve",
+            TransactionType::ChargeBack { .. } => "chargeback",
+            TransactionType::Transfer { .. } => "transfer",
+            TransactionType::Fee { .. } => "fee",
+            TransactionType::Lock => "lock",
+            TransactionType::Unlock => "unlock",
+            TransactionType::Adjustment { .. } => "adjustment",
+            TransactionType::Hold { .. } => "hold",
+            TransactionType::Release => "release",
+            TransactionType::Convert { .. } => "convert",
+        }
+    }
+
+    /// the amount given to a dispute, resolve or chargeback, if any
+    pub fn partial_amount(&self) -> Option<Amount> {
+        match self {
+            TransactionType::Dispute { amount } | TransactionType::Resolve { amount } | TransactionType::ChargeBack { amount } => *amount,
+            _ => None,
+        }
+    }
+
+    /// administrative transactions can only be applied by an operator
+    pub fn is_administrative(&self) -> bool {
+        matches!(self, TransactionType::Lock | TransactionType::Unlock | TransactionType::Adjustment { .. })
+    }
+}
+
+/// `Origin` tells who a transaction came from. Transactions from partners
+/// are the ones in the input files, while operators can also apply the
+/// administrative transactions
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Origin {
+    #[default]
+    Partner,
+    Operator,
 }
 
 /// `Transaction` represents the details of a particular transaction
@@ -98,11 +635,17 @@ pub enum TransactionType {
 /// that as None for Dispute, Resolve and ChargeBack but keeping the
 /// transaction type separate kind of gaurantees us that no one will
 /// ever set the amount for these type transactions
-#[derive(Debug)]
+///
+/// The optional timestamp (seconds since the unix epoch) is only used for
+/// ordering transactions coming from multiple sources
+#[derive(Debug, Clone, PartialEq)]
 pub struct Transaction {
     pub client : ClientId,
     pub tx : TransactionId,
-    pub txn_type : TransactionType
+    pub txn_type : TransactionType,
+    pub timestamp : Option<Timestamp>,
+    pub origin : Origin,
+    pub currency : Currency,
 }
 
 impl Transaction {
@@ -110,112 +653,231 @@ impl Transaction {
         Transaction {
             client : client,
             tx : id,
-            txn_type : transaction_type
+            txn_type : transaction_type,
+            timestamp : None,
+            origin : Origin::Partner,
+            currency : Currency::DEFAULT,
         }
     }
+
+    /// sets the currency of the transaction's amount
+    pub fn with_currency(mut self, currency : Currency) -> Self {
+        self.currency = currency;
+        self
+    }
+
+    /// sets who the transaction came from
+    pub fn with_origin(mut self, origin : Origin) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    /// sets the time at which the transaction took place
+    pub fn with_timestamp(mut self, timestamp : Timestamp) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
 }
 
 impl<'a> Deserialize<'a> for Transaction {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> 
         where D: Deserializer<'a> 
     { 
-        #[derive(Deserialize)]
-        #[serde(field_identifier, rename_all = "lowercase")]
-        enum Field { 
-            Type,
-            Client, 
-            Tx, 
-            Amount 
-        }
+        deserialize_transaction(deserializer, FieldFormat::Any)
+    }
+}
 
-        struct TransactionVisitor;
+/// `CsvTransaction` is a Transaction read from a csv record, whose fields are
+/// all text and are parsed as they are written, e.g. a reason of `001`
+pub(crate) struct CsvTransaction(pub(crate) Transaction);
 
-        impl<'a> Visitor<'a> for TransactionVisitor {
-            type Value = Transaction;
-            
-            fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> { 
-                formatter.write_str("map")
-            }
+impl<'a> Deserialize<'a> for CsvTransaction {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'a>
+    {
+        deserialize_transaction(deserializer, FieldFormat::Text).map(CsvTransaction)
+    }
+}
 
-            fn visit_map<V>(self, mut map: V) -> Result<Transaction, V::Error>
-            where
-                V: MapAccess<'a>,
-            {
-                // define all field options and check afterwards to make
-                // suree all are present in a given record
-                let mut transaction_field : Option<&str> = None;
-                let mut client_field : Option<ClientId> = None;
-                let mut tx_id_field : Option<TransactionId> = None;
-                let mut amount_field : Option<Amount> = None;
-
-                while let Some(key) = map.next_key()? {
-                    match key {
-                        Field::Type => {
-                            transaction_field = map.next_value()?;
-                        },
-                        Field::Client => {
-                            client_field = parse_next(&mut map)?;
-                        },
-                        Field::Tx => {
-                            tx_id_field = parse_next(&mut map)?;
-                        },
-                        Field::Amount => {
-                            amount_field = parse_next(&mut map)?;
-                        },
-                    }
-                }
-                
-                // each transaction must have a transaction type, client and transaction ID
-                let txn_type = transaction_field.ok_or(de::Error::missing_field("type"))?;
-                let client = client_field.ok_or(de::Error::missing_field("client"))?;
-                let tx_id = tx_id_field.ok_or(de::Error::missing_field("tx"))?;
-
-                let transaction = match txn_type{
-                    "withdrawal" | "deposit" => {
-                        // a deposit / withdrawal must have the amount field in the incoming
-                        // record
-                        let amount = amount_field.ok_or(de::Error::missing_field("amount"))?;
-                        if *amount < 0.0 {
-                            return Err(de::Error::invalid_value(
-                                        serde::de::Unexpected::Float(*amount as f64), 
-                                        &"a positive number"));
-                        }
-            
-                        if txn_type == "deposit" {
-                            Transaction::new(client, tx_id, TransactionType::Deposit { amount: amount })
-                        }
-                        else {
-                            Transaction::new(client, tx_id, TransactionType::Withdrawal { amount: amount })
-                        }
+/// `deserialize_transaction` reads a Transaction whose fields are given in
+/// the given format
+fn deserialize_transaction<'a, D>(deserializer: D, format : FieldFormat) -> Result<Transaction, D::Error>
+    where D: Deserializer<'a>
+{
+    #[derive(Deserialize)]
+    #[serde(field_identifier, rename_all = "lowercase")]
+    enum Field { 
+        Type,
+        Client, 
+        Tx, 
+        Amount,
+        Timestamp,
+        #[serde(rename = "to_client")]
+        ToClient,
+        Reason,
+        Expires,
+        Currency,
+        #[serde(rename = "to_currency")]
+        ToCurrency,
+    }
+
+    struct TransactionVisitor {
+        format : FieldFormat,
+    }
+
+    impl<'a> Visitor<'a> for TransactionVisitor {
+        type Value = Transaction;
+        
+        fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> { 
+            formatter.write_str("map")
+        }
+
+        fn visit_map<V>(self, mut map: V) -> Result<Transaction, V::Error>
+        where
+            V: MapAccess<'a>,
+        {
+            // define all field options and check afterwards to make
+            // suree all are present in a given record
+            let mut transaction_field : Option<&str> = None;
+            let mut client_field : Option<ClientId> = None;
+            let mut tx_id_field : Option<TransactionId> = None;
+            let mut amount_field : Option<Amount> = None;
+            let mut timestamp_field : Option<Timestamp> = None;
+            let mut to_client_field : Option<ClientId> = None;
+            let mut reason_field : Option<String> = None;
+            let mut expires_field : Option<Timestamp> = None;
+            let mut currency_field : Option<Currency> = None;
+            let mut to_currency_field : Option<Currency> = None;
+
+            while let Some(key) = map.next_key()? {
+                match key {
+                    Field::Type => {
+                        transaction_field = map.next_value()?;
+                    },
+                    Field::Client => {
+                        client_field = parse_next(&mut map, self.format)?;
+                    },
+                    Field::Tx => {
+                        tx_id_field = parse_next(&mut map, self.format)?;
+                    },
+                    Field::Amount => {
+                        amount_field = parse_next(&mut map, self.format)?;
+                    },
+                    Field::Timestamp => {
+                        timestamp_field = parse_next(&mut map, self.format)?;
+                    },
+                    Field::ToClient => {
+                        to_client_field = parse_next(&mut map, self.format)?;
+                    },
+                    Field::Reason => {
+                        reason_field = parse_next(&mut map, self.format)?;
                     },
-                    "dispute" => {
-                        Transaction::new(client, tx_id, TransactionType::Dispute)
+                    Field::Expires => {
+                        expires_field = parse_next(&mut map, self.format)?;
                     },
-                    "resolve" => {
-                        Transaction::new(client, tx_id, TransactionType::Resolve)
+                    Field::Currency => {
+                        currency_field = parse_next(&mut map, self.format)?;
                     },
-                    "chargeback" => {
-                        Transaction::new(client, tx_id, TransactionType::ChargeBack)
+                    Field::ToCurrency => {
+                        to_currency_field = parse_next(&mut map, self.format)?;
                     },
-                    invalid_type => {
+                }
+            }
+            
+            // each transaction must have a transaction type, client and transaction ID
+            let txn_type = transaction_field.ok_or(de::Error::missing_field("type"))?;
+            let client = client_field.ok_or(de::Error::missing_field("client"))?;
+            let tx_id = tx_id_field.ok_or(de::Error::missing_field("tx"))?;
+
+            // amounts are kept to the minor units of their currency
+            let currency = currency_field.unwrap_or_default();
+            let amount_field = amount_field.map(|amount| amount.round_to(currency));
+
+            let mut transaction = match txn_type{
+                "withdrawal" | "deposit" | "transfer" | "fee" | "hold" | "convert" => {
+                    // a deposit / withdrawal must have the amount field in the incoming
+                    // record
+                    let amount = amount_field.ok_or(de::Error::missing_field("amount"))?;
+                    if *amount < 0.0 {
                         return Err(de::Error::invalid_value(
-                            serde::de::Unexpected::Other(invalid_type), &"type of known transaction"));
+                                    serde::de::Unexpected::Float(*amount as f64), 
+                                    &"a positive number"));
+                    }
+        
+                    if txn_type == "deposit" {
+                        Transaction::new(client, tx_id, TransactionType::Deposit { amount: amount })
+                    }
+                    else if txn_type == "withdrawal" {
+                        Transaction::new(client, tx_id, TransactionType::Withdrawal { amount: amount })
+                    }
+                    else if txn_type == "fee" {
+                        Transaction::new(client, tx_id, TransactionType::Fee { amount })
+                    }
+                    else if txn_type == "hold" {
+                        Transaction::new(client, tx_id, TransactionType::Hold { amount, expires : expires_field })
+                    }
+                    else if txn_type == "convert" {
+                        // a conversion must say which currency it converts to
+                        let to = to_currency_field.ok_or(de::Error::missing_field("to_currency"))?;
+                        Transaction::new(client, tx_id, TransactionType::Convert { amount, to })
+                    }
+                    else {
+                        // a transfer must also say who the money is going to
+                        let to_client = to_client_field.ok_or(de::Error::missing_field("to_client"))?;
+                        Transaction::new(client, tx_id, TransactionType::Transfer { to_client, amount })
+                    }
+                },
+                "dispute" | "resolve" | "chargeback" => {
+                    // the amount is optional, for partial disputes
+                    if let Some(amount) = amount_field.filter(|amount| **amount < 0.0) {
+                        return Err(de::Error::invalid_value(
+                                    serde::de::Unexpected::Float(*amount as f64),
+                                    &"a positive number"));
                     }
-                };
 
-                Ok(transaction)
-            }
-        }
+                    let amount = amount_field;
+                    let txn_type = match txn_type {
+                        "dispute" => TransactionType::Dispute { amount },
+                        "resolve" => TransactionType::Resolve { amount },
+                        _ => TransactionType::ChargeBack { amount },
+                    };
+                    Transaction::new(client, tx_id, txn_type)
+                },
+                "lock" => {
+                    Transaction::new(client, tx_id, TransactionType::Lock)
+                },
+                "unlock" => {
+                    Transaction::new(client, tx_id, TransactionType::Unlock)
+                },
+                "release" => {
+                    Transaction::new(client, tx_id, TransactionType::Release)
+                },
+                "adjustment" => {
+                    // an adjustment can be negative but must always say why it was made
+                    let amount = amount_field.ok_or(de::Error::missing_field("amount"))?;
+                    let reason = reason_field.ok_or(de::Error::missing_field("reason"))?;
+                    Transaction::new(client, tx_id, TransactionType::Adjustment { amount, reason })
+                },
+                invalid_type => {
+                    return Err(de::Error::invalid_value(
+                        serde::de::Unexpected::Other(invalid_type), &"type of known transaction"));
+                }
+            };
 
-        // define fields that should be present in the map
-        const FIELDS : &'static [&'static str] = &["type", "client", "tx", "amount"];
-        deserializer.deserialize_struct("Transaction", FIELDS, TransactionVisitor)
+            transaction.timestamp = timestamp_field;
+            transaction.currency = currency;
+            Ok(transaction)
+        }
     }
+
+    // define fields that should be present in the map
+    const FIELDS : &'static [&'static str] = &["type", "client", "tx", "amount", "timestamp", "to_client", "reason", "expires", "currency", "to_currency"];
+    deserializer.deserialize_struct("Transaction", FIELDS, TransactionVisitor { format })
 }
 
 /// `parse_next` is a generic that is ued for triming and then converting
 /// the string part into the given type T
-fn parse_next<'a, V, T>(map: &mut V) -> Result<Option<T>, V::Error>
+fn parse_next<'a, V, T>(map: &mut V, format : FieldFormat) -> Result<Option<T>, V::Error>
 where
     V: MapAccess<'a>,
     T: std::str::FromStr,
@@ -224,7 +886,8 @@ where
     // trim the sting part and in case it is empty return None, this is
     // particular useful for amount field as that is not present in the
     // dispute, resolve and chargeback transactions
-    let trimmed_val = map.next_value::<&str>()?.trim();
+    let FieldText(value) = map.next_value_seed(format)?;
+    let trimmed_val = value.trim();
     if trimmed_val.len() == 0 {
         return Ok(None);
     }
@@ -239,3 +902,74 @@ where
     Ok(Some(parsed_val))
 }
 
+/// `FieldFormat` is how the fields of a transaction are given
+#[derive(Clone, Copy)]
+enum FieldFormat {
+    /// as any type of the format, e.g. the numbers and booleans of json
+    Any,
+    /// always as text, as in csv
+    Text,
+}
+
+/// `FieldText` is the text of a field before it is parsed. Csv gives all
+/// fields as strings, but other formats such as json lines can also give
+/// numbers, booleans or nulls, which are converted to their text form
+struct FieldText<'a>(Cow<'a, str>);
+
+impl<'a> DeserializeSeed<'a> for FieldFormat {
+    type Value = FieldText<'a>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where D: Deserializer<'a>
+    {
+        // text is read as it is, as csv would otherwise turn e.g. `001`
+        // into the number 1
+        match self {
+            FieldFormat::Any => deserializer.deserialize_any(FieldTextVisitor),
+            FieldFormat::Text => deserializer.deserialize_str(FieldTextVisitor),
+        }
+    }
+}
+
+struct FieldTextVisitor;
+
+impl<'a> Visitor<'a> for FieldTextVisitor {
+    type Value = FieldText<'a>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter.write_str("a string, a number or a boolean")
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, value: &'a str) -> Result<Self::Value, E> {
+        Ok(FieldText(Cow::Borrowed(value)))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        Ok(FieldText(Cow::Owned(value.to_string())))
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Self::Value, E> {
+        Ok(FieldText(Cow::Borrowed(if value { "true" } else { "false" })))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+        Ok(FieldText(Cow::Owned(value.to_string())))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+        Ok(FieldText(Cow::Owned(value.to_string())))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Self::Value, E> {
+        Ok(FieldText(Cow::Owned(value.to_string())))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(FieldText(Cow::Borrowed("")))
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(FieldText(Cow::Borrowed("")))
+    }
+}
+
@@ -0,0 +1,56 @@
+//! Observers of the engine.
+//!
+//! An `EngineObserver` registered on a `TransactionEngine` is told about
+//! every transaction given to `apply`, along with the client's balance before
+//! and after it. The balance is the one in the currency the transaction was
+//! applied in: that of the transaction it refers to for a dispute, resolve,
+//! chargeback or release, the sender's for a transfer and the source currency
+//! for a conversion. The receiver of a transfer, or of a dispute, resolve or
+//! chargeback of one, is observed by `on_counterparty_applied`, which a
+//! `ShardedEngine` calls on the shard of the receiver. Holds that expire and
+//! interest that is posted do not come from a transaction and are not observed.
+use std::fmt;
+use std::sync::Arc;
+
+use super::Transaction;
+use super::ledger::ClientBalance;
+
+/// `EngineObserver` is called back by the engine. All callbacks do nothing
+/// by default
+pub trait EngineObserver : fmt::Debug + Send {
+    /// the transaction has been applied
+    fn on_applied(&mut self, _transaction : &Transaction, _before : &ClientBalance, _after : &ClientBalance) {}
+
+    /// the transaction could not be applied, or was rejected by the screening
+    fn on_rejected(&mut self, _transaction : &Transaction, _error : &crate::Error) {}
+
+    /// the transaction locked the client's account
+    fn on_locked(&mut self, _transaction : &Transaction, _balance : &ClientBalance) {}
+
+    /// a dispute has been applied
+    fn on_dispute_opened(&mut self, _transaction : &Transaction, _before : &ClientBalance, _after : &ClientBalance) {}
+
+    /// a transaction of another client has been applied to the client it was
+    /// sent to, e.g. a transfer, with the receiver's balance before and after
+    fn on_counterparty_applied(&mut self, _transaction : &Transaction, _before : &ClientBalance, _after : &ClientBalance) {}
+}
+
+/// `ObserverFactory` creates the observer of each shard of a `ShardedEngine`
+#[derive(Clone)]
+pub struct ObserverFactory(Arc<dyn Fn() -> Box<dyn EngineObserver> + Send + Sync>);
+
+impl ObserverFactory {
+    pub fn new(create : impl Fn() -> Box<dyn EngineObserver> + Send + Sync + 'static) -> Self {
+        ObserverFactory(Arc::new(create))
+    }
+
+    pub fn create(&self) -> Box<dyn EngineObserver> {
+        (self.0)()
+    }
+}
+
+impl fmt::Debug for ObserverFactory {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ObserverFactory")
+    }
+}
@@ -0,0 +1,96 @@
+//! Exchange rates for currency conversions.
+//!
+//! A `RateTable` keeps the rates between pairs of currencies along with the
+//! time from which each rate is effective. A conversion uses the latest rate
+//! effective at the time of the transaction, and only the direction given in
+//! the table, i.e. a rate from `EUR` to `USD` does not convert `USD` to `EUR`.
+//!
+//! The converted amount is rounded half away from zero to the minor units of
+//! the target currency. The rate and the rounding are done in `f64`, so the
+//! same amount and rate always give the same result.
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use super::Timestamp;
+use super::amount::Amount;
+use super::currency::Currency;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RateTable {
+    /// rates of each pair, sorted by when they become effective
+    rates : HashMap<(Currency, Currency), Vec<(Timestamp, f64)>>,
+}
+
+/// a single line of a rates file
+#[derive(Debug, Deserialize)]
+struct RateRecord {
+    from : Option<String>,
+    to : Option<String>,
+    rate : f64,
+    effective : Option<Timestamp>,
+}
+
+/// an empty code is the default currency
+fn parse_currency(code : Option<String>) -> crate::Result<Currency> {
+    match code {
+        Some(code) => Ok(code.parse::<Currency>()?),
+        None => Ok(Currency::DEFAULT),
+    }
+}
+
+impl RateTable {
+    pub fn new() -> Self {
+        RateTable::default()
+    }
+
+    /// adds the rate of converting one unit of `from` to `to`, effective
+    /// from the given time
+    pub fn with_rate(mut self, from : Currency, to : Currency, rate : f64, effective : Timestamp) -> crate::Result<Self> {
+        if from == to {
+            return Err(format!("Rate from {} to itself", from).into());
+        }
+        if !rate.is_finite() || rate <= 0.0 {
+            return Err(format!("Rate from {} to {} is not a positive number", from, to).into());
+        }
+
+        let rates = self.rates.entry((from, to)).or_default();
+        let at = rates.partition_point(|(time, _)| *time <= effective);
+        rates.insert(at, (effective, rate));
+        Ok(self)
+    }
+
+    /// `from_csv` loads the rates from a csv file with the columns
+    /// `from,to,rate,effective`. An empty currency is the default currency
+    /// and a rate without `effective` applies from the start
+    pub fn from_csv(path : &str) -> crate::Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_path(path)?;
+
+        reader.deserialize::<RateRecord>()
+            .try_fold(RateTable::new(), |table, record| {
+                let record = record?;
+                let (from, to) = (parse_currency(record.from)?, parse_currency(record.to)?);
+                table.with_rate(from, to, record.rate, record.effective.unwrap_or(0))
+            })
+    }
+
+    /// the rate effective at the given time along with when it became
+    /// effective. Without a time the latest rate is used
+    pub fn rate(&self, from : Currency, to : Currency, at : Option<Timestamp>) -> Option<(f64, Timestamp)> {
+        let rates = self.rates.get(&(from, to))?;
+        let effective = match at {
+            Some(at) => &rates[..rates.partition_point(|(time, _)| *time <= at)],
+            None => rates,
+        };
+        effective.last().map(|(time, rate)| (*rate, *time))
+    }
+}
+
+/// converts the amount at the given rate, rounded half away from zero to
+/// the minor units of the target currency
+pub fn convert(amount : Amount, rate : f64, to : Currency) -> Amount {
+    let scale = 10f64.powi(to.minor_units() as i32);
+    Amount::new((((*amount as f64) * rate * scale).round() / scale) as f32)
+}
@@ -0,0 +1,341 @@
+//! Screening of transactions before they are applied.
+//!
+//! A `Screening` looks at every transaction before the engine applies it and
+//! allows it, rejects it or flags it. A flagged transaction is still applied,
+//! and is kept by the engine so that it can be reported on its own. `Rules`
+//! runs a set of screenings, e.g. the built-in ones:
+//!
+//! |Rule|Catches|
+//! |-|-|
+//! |`QuickWithdrawal`|a withdrawal of at least a deposit made shortly before it|
+//! |`DisputeCount`|a client disputing more than a number of transactions|
+//! |`RoundAmountBurst`|a number of round deposits / withdrawals in a short time|
+//!
+//! Screenings can keep state of their own, which is kept per client by the
+//! built-in rules so that each shard of a `ShardedEngine` can have its own.
+//! `screen` only looks at the state, which is updated by `applied` once the
+//! engine has applied the transaction, so a transaction that is rejected,
+//! by a rule or by the engine, is not remembered.
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::Arc;
+
+use super::{ClientId, Timestamp, Transaction, TransactionId, TransactionType};
+use super::amount::Amount;
+use super::interest::DAY;
+use super::ledger::ClientLedger;
+
+/// `Verdict` is what a screening decides for a transaction
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Allow,
+    /// applied, but reported along with the reason
+    Flag(String),
+    /// not applied
+    Reject(String),
+}
+
+/// `Screening` is run on each transaction before it is applied
+pub trait Screening : fmt::Debug + Send {
+    /// screens a transaction given the ledger of its client, if it has one.
+    /// `now` is the timestamp of the transaction, or the engine's clock when
+    /// it has none
+    fn screen(&self, transaction : &Transaction, ledger : Option<&ClientLedger>, now : Option<Timestamp>) -> Verdict;
+
+    /// called once a transaction that was allowed or flagged has been
+    /// applied, with the ledger of its client afterwards
+    fn applied(&mut self, _transaction : &Transaction, _ledger : Option<&ClientLedger>, _now : Option<Timestamp>) {}
+
+    /// a copy of the screening along with its state, taken before a batch so
+    /// that rolling the batch back also forgets what the screening saw of it.
+    /// A screening without one keeps the state of rolled back transactions
+    fn snapshot(&self) -> Option<Box<dyn Screening>> {
+        None
+    }
+}
+
+/// `Action` is what a built-in rule does with a transaction it catches
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Action {
+    #[default]
+    Flag,
+    Reject,
+}
+
+impl Action {
+    /// `parse` reads `flag` or `reject`
+    pub fn parse(name : &str) -> crate::Result<Self> {
+        match name {
+            "flag" => Ok(Action::Flag),
+            "reject" => Ok(Action::Reject),
+            other => Err(format!("Unknown screening action {}", other).into()),
+        }
+    }
+
+    fn verdict(self, reason : String) -> Verdict {
+        match self {
+            Action::Flag => Verdict::Flag(reason),
+            Action::Reject => Verdict::Reject(reason),
+        }
+    }
+}
+
+/// `QuickWithdrawal` catches a withdrawal of at least the amount of the
+/// client's last deposit, within `within` seconds of it
+#[derive(Debug, Clone)]
+pub struct QuickWithdrawal {
+    pub within : Timestamp,
+    pub action : Action,
+    deposits : HashMap<ClientId, (Timestamp, Amount)>,
+}
+
+impl QuickWithdrawal {
+    pub fn new(within : Timestamp, action : Action) -> Self {
+        QuickWithdrawal {
+            within,
+            action,
+            deposits : HashMap::new(),
+        }
+    }
+}
+
+impl Screening for QuickWithdrawal {
+    fn screen(&self, transaction : &Transaction, _ledger : Option<&ClientLedger>, now : Option<Timestamp>) -> Verdict {
+        let (Some(now), TransactionType::Withdrawal { amount }) = (now, &transaction.txn_type) else {
+            return Verdict::Allow;
+        };
+
+        // a withdrawal timed before the deposit is not within the window
+        if let Some((deposited_at, deposit)) = self.deposits.get(&transaction.client) {
+            let elapsed = now.checked_sub(*deposited_at);
+            if let Some(elapsed) = elapsed.filter(|elapsed| *elapsed <= self.within && amount >= deposit) {
+                return self.action.verdict(format!("withdrawal of {} within {} seconds of a deposit of {}",
+                    amount, elapsed, deposit));
+            }
+        }
+        Verdict::Allow
+    }
+
+    fn applied(&mut self, transaction : &Transaction, _ledger : Option<&ClientLedger>, now : Option<Timestamp>) {
+        if let (Some(now), TransactionType::Deposit { amount }) = (now, &transaction.txn_type) {
+            self.deposits.insert(transaction.client, (now, *amount));
+        }
+    }
+
+    fn snapshot(&self) -> Option<Box<dyn Screening>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+/// `DisputeCount` catches a client disputing more than `max` transactions
+#[derive(Debug, Clone)]
+pub struct DisputeCount {
+    pub max : usize,
+    pub action : Action,
+    disputed : HashMap<ClientId, HashSet<TransactionId>>,
+}
+
+impl DisputeCount {
+    pub fn new(max : usize, action : Action) -> Self {
+        DisputeCount {
+            max,
+            action,
+            disputed : HashMap::new(),
+        }
+    }
+}
+
+impl Screening for DisputeCount {
+    /// only a dispute of a transaction the client has, and that it has not
+    /// disputed before, adds to the count
+    fn screen(&self, transaction : &Transaction, ledger : Option<&ClientLedger>, _now : Option<Timestamp>) -> Verdict {
+        let TransactionType::Dispute { .. } = transaction.txn_type else {
+            return Verdict::Allow;
+        };
+        if ledger.and_then(|ledger| ledger.get_record(transaction.tx)).is_none() {
+            return Verdict::Allow;
+        }
+
+        let disputed = self.disputed.get(&transaction.client);
+        if disputed.is_some_and(|disputed| disputed.contains(&transaction.tx)) {
+            return Verdict::Allow;
+        }
+        let count = disputed.map(HashSet::len).unwrap_or(0) + 1;
+        if count > self.max {
+            return self.action.verdict(format!("client has disputed {} transactions", count));
+        }
+        Verdict::Allow
+    }
+
+    fn applied(&mut self, transaction : &Transaction, ledger : Option<&ClientLedger>, _now : Option<Timestamp>) {
+        if let TransactionType::Dispute { .. } = transaction.txn_type {
+            if ledger.and_then(|ledger| ledger.get_record(transaction.tx)).is_some() {
+                self.disputed.entry(transaction.client).or_default().insert(transaction.tx);
+            }
+        }
+    }
+
+    fn snapshot(&self) -> Option<Box<dyn Screening>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+/// `RoundAmountBurst` catches the `count`th deposit / withdrawal of a
+/// multiple of `unit` within `within` seconds
+#[derive(Debug, Clone)]
+pub struct RoundAmountBurst {
+    pub unit : Amount,
+    pub count : usize,
+    pub within : Timestamp,
+    pub action : Action,
+    seen : HashMap<ClientId, VecDeque<Timestamp>>,
+}
+
+impl RoundAmountBurst {
+    pub fn new(unit : Amount, count : usize, within : Timestamp, action : Action) -> Self {
+        RoundAmountBurst {
+            unit,
+            count,
+            within,
+            action,
+            seen : HashMap::new(),
+        }
+    }
+
+    fn is_round(&self, amount : Amount) -> bool {
+        *amount > 0.0 && Amount::new((*amount / *self.unit).round() * *self.unit) == amount
+    }
+
+    /// the time of a timed deposit / withdrawal of a round amount
+    fn round_at(&self, transaction : &Transaction, now : Option<Timestamp>) -> Option<Timestamp> {
+        match (now, &transaction.txn_type) {
+            (Some(now), TransactionType::Deposit { amount } | TransactionType::Withdrawal { amount }) if self.is_round(*amount) => Some(now),
+            _ => None,
+        }
+    }
+}
+
+impl Screening for RoundAmountBurst {
+    fn screen(&self, transaction : &Transaction, _ledger : Option<&ClientLedger>, now : Option<Timestamp>) -> Verdict {
+        let Some(now) = self.round_at(transaction, now) else {
+            return Verdict::Allow;
+        };
+
+        let seen = self.seen.get(&transaction.client)
+            .map(|seen| seen.iter().filter(|at| **at + self.within >= now).count())
+            .unwrap_or(0);
+        if seen + 1 >= self.count {
+            return self.action.verdict(format!("{} round amounts within {} seconds", seen + 1, self.within));
+        }
+        Verdict::Allow
+    }
+
+    fn applied(&mut self, transaction : &Transaction, _ledger : Option<&ClientLedger>, now : Option<Timestamp>) {
+        let Some(now) = self.round_at(transaction, now) else {
+            return;
+        };
+
+        let seen = self.seen.entry(transaction.client).or_default();
+        while seen.front().is_some_and(|at| at + self.within < now) {
+            seen.pop_front();
+        }
+        seen.push_back(now);
+    }
+
+    fn snapshot(&self) -> Option<Box<dyn Screening>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+/// `Rules` runs every screening on each transaction. A rejection wins over
+/// a flag, and the reasons of all rules that caught it are given
+#[derive(Debug, Default)]
+pub struct Rules {
+    rules : Vec<Box<dyn Screening>>,
+}
+
+impl Rules {
+    pub fn new() -> Self {
+        Rules::default()
+    }
+
+    pub fn with_rule(mut self, rule : impl Screening + 'static) -> Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    /// the built-in rules: a withdrawal within an hour of a deposit of the
+    /// same amount, more than 3 disputes and 3 round amounts of 1000 in a day
+    pub fn builtin(action : Action) -> Self {
+        Rules::new()
+            .with_rule(QuickWithdrawal::new(60 * 60, action))
+            .with_rule(DisputeCount::new(3, action))
+            .with_rule(RoundAmountBurst::new(Amount::new(1000.0), 3, DAY, action))
+    }
+}
+
+impl Screening for Rules {
+    fn screen(&self, transaction : &Transaction, ledger : Option<&ClientLedger>, now : Option<Timestamp>) -> Verdict {
+        let mut flagged = Vec::new();
+        let mut rejected = Vec::new();
+
+        for rule in self.rules.iter() {
+            match rule.screen(transaction, ledger, now) {
+                Verdict::Allow => {},
+                Verdict::Flag(reason) => flagged.push(reason),
+                Verdict::Reject(reason) => rejected.push(reason),
+            }
+        }
+
+        if !rejected.is_empty() {
+            Verdict::Reject(rejected.join("; "))
+        }
+        else if !flagged.is_empty() {
+            Verdict::Flag(flagged.join("; "))
+        }
+        else {
+            Verdict::Allow
+        }
+    }
+
+    fn applied(&mut self, transaction : &Transaction, ledger : Option<&ClientLedger>, now : Option<Timestamp>) {
+        for rule in self.rules.iter_mut() {
+            rule.applied(transaction, ledger, now);
+        }
+    }
+
+    /// only when every rule has a snapshot
+    fn snapshot(&self) -> Option<Box<dyn Screening>> {
+        let rules = self.rules.iter().map(|rule| rule.snapshot()).collect::<Option<Vec<_>>>()?;
+        Some(Box::new(Rules { rules }))
+    }
+}
+
+/// `Flagged` is a transaction flagged by the screening
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flagged {
+    pub client : ClientId,
+    pub tx : TransactionId,
+    pub txn_type : &'static str,
+    pub reason : String,
+}
+
+/// `ScreeningFactory` creates the screening of each shard of a `ShardedEngine`
+#[derive(Clone)]
+pub struct ScreeningFactory(Arc<dyn Fn() -> Box<dyn Screening> + Send + Sync>);
+
+impl ScreeningFactory {
+    pub fn new(create : impl Fn() -> Box<dyn Screening> + Send + Sync + 'static) -> Self {
+        ScreeningFactory(Arc::new(create))
+    }
+
+    pub fn create(&self) -> Box<dyn Screening> {
+        (self.0)()
+    }
+}
+
+impl fmt::Debug for ScreeningFactory {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ScreeningFactory")
+    }
+}
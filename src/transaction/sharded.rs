@@ -0,0 +1,278 @@
+//! `txengine::transaction::sharded::ShardedEngine`
+//!
+//! Clients are independent of each other, so their transactions can be
+//! applied in parallel. The sharded engine hashes each transaction's client
+//! to one of N worker threads, each of which owns a `TransactionEngine` for
+//! its subset of clients.
+//!
+//! The calling thread reads the incoming transactions and feeds the workers
+//! over bounded channels, so a slow worker applies back-pressure on the
+//! reader instead of letting the queue grow without limit. As a client is
+//! always routed to the same worker, and each channel is FIFO, the
+//! transactions of a client are applied in the order they were read.
+//!
+//! A transfer between clients of two different shards, or a dispute of such
+//! a transfer, is applied by the reader itself. It first waits for both
+//! shards to finish what has been queued for them and then applies the
+//! transaction across both of their engines, so it is still atomic. The
+//! reader does not feed anyone while doing so, which makes cross shard
+//! transfers a lot slower than other transactions.
+//!
+//! A shard is only told that time has moved forward when it is next given
+//! something to apply, and once more at the end. It is then advanced through
+//! the last timestamp the reader saw on each day since, which is all that
+//! holds expiring by their own time and interest accruing by whole days
+//! need, so they end up the same as they would on a single engine.
+use std::collections::HashMap;
+use std::sync::{mpsc, Mutex};
+use std::thread;
+
+use super::{ClientId, Timestamp, Transaction, TransactionEngine, TransactionId, TransactionType};
+use super::config::EngineConfig;
+use super::interest::DAY;
+use super::observer::ObserverFactory;
+use super::screening::ScreeningFactory;
+
+/// default number of transactions that can be queued up for each worker
+const DEFAULT_CHANNEL_CAPACITY : usize = 1024;
+
+/// `Message` is what the reader sends to a worker
+enum Message<P> {
+    Apply(P, Transaction),
+    /// moves the clock of the worker's engine forward through each timestamp
+    Advance(Vec<Timestamp>),
+    /// the worker replies once everything queued before it has been applied
+    Sync(mpsc::SyncSender<()>),
+}
+
+#[derive(Debug, Clone)]
+pub struct ShardedEngine {
+    shards : usize,
+    channel_capacity : usize,
+    config : EngineConfig,
+    screening : Option<ScreeningFactory>,
+    observers : Vec<ObserverFactory>,
+}
+
+impl ShardedEngine {
+    /// creates an engine with the given number of worker shards. At least
+    /// one shard is always used
+    pub fn new(shards : usize) -> Self {
+        ShardedEngine {
+            shards : shards.max(1),
+            channel_capacity : DEFAULT_CHANNEL_CAPACITY,
+            config : EngineConfig::default(),
+            screening : None,
+            observers : Vec::new(),
+        }
+    }
+
+    /// sets the config used by the engine of every shard
+    pub fn with_config(mut self, config : EngineConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// gives the engine of every shard a screening of its own
+    pub fn with_screening(mut self, screening : ScreeningFactory) -> Self {
+        self.screening = Some(screening);
+        self
+    }
+
+    /// gives the engine of every shard an observer of its own. A cross shard
+    /// transfer is observed by the shard of the sender, and by the shard of
+    /// the receiver through `on_counterparty_applied`, as on a single engine
+    pub fn with_observer(mut self, observer : ObserverFactory) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    /// sets how many transactions can be queued up for each worker before
+    /// the reader has to wait
+    pub fn with_channel_capacity(mut self, capacity : usize) -> Self {
+        self.channel_capacity = capacity.max(1);
+        self
+    }
+
+    /// returns the shard that owns the given client
+    pub fn shard_of(&self, client : ClientId) -> usize {
+        client as usize % self.shards
+    }
+
+    /// `process` applies all transactions across the worker shards and returns
+    /// a single `TransactionEngine` holding the balances of all clients.
+    ///
+    /// Each transaction comes along with a context `P` (e.g. where it was read
+    /// from) which is handed back to `on_rejected` when the transaction could
+    /// not be applied. `on_rejected` is called from the worker threads, and
+    /// from the calling thread for cross shard transfers.
+    pub fn process<I, P, F>(&self, transactions : I, on_rejected : F) -> crate::Result<TransactionEngine>
+        where
+            I : Iterator<Item = (P, Transaction)>,
+            P : Send,
+            F : Fn(P, crate::Error) + Sync
+    {
+        let on_rejected = &on_rejected;
+        let engines : Vec<Mutex<TransactionEngine>> = (0..self.shards)
+            .map(|_| {
+                let mut engine = TransactionEngine::with_config(self.config.clone());
+                if let Some(screening) = &self.screening {
+                    engine = engine.with_screening(screening.create());
+                }
+                for observer in &self.observers {
+                    engine = engine.with_observer(observer.create());
+                }
+                Mutex::new(engine)
+            })
+            .collect();
+
+        thread::scope(|scope| {
+            let mut senders = Vec::with_capacity(self.shards);
+            let mut workers = Vec::with_capacity(self.shards);
+
+            for engine in &engines {
+                let (sender, receiver) = mpsc::sync_channel::<Message<P>>(self.channel_capacity);
+                senders.push(sender);
+
+                workers.push(scope.spawn(move || {
+                    for message in receiver {
+                        match message {
+                            Message::Apply(context, transaction) => {
+                                let result = engine.lock()
+                                    .map_err(|_| crate::Error::from("Shard engine is unavailable"))
+                                    .and_then(|mut engine| engine.apply(transaction));
+                                if let Err(e) = result {
+                                    on_rejected(context, e);
+                                }
+                            },
+                            Message::Advance(times) => {
+                                if let Ok(mut engine) = engine.lock() {
+                                    for now in times {
+                                        engine.advance_to(now);
+                                    }
+                                }
+                            },
+                            Message::Sync(reply) => {
+                                let _ = reply.send(());
+                            },
+                        }
+                    }
+                }));
+            }
+
+            // transfers whose sender and receiver are on different shards,
+            // so that their disputes are applied across the shards as well
+            let mut cross_shard : HashMap<TransactionId, ClientId> = HashMap::new();
+            let mut clock = Clock::new(self.shards);
+
+            for (context, transaction) in transactions {
+                let shard = self.shard_of(transaction.client);
+                if let Some(timestamp) = transaction.timestamp {
+                    clock.see(timestamp);
+                }
+
+                let counterparty = match transaction.txn_type {
+                    TransactionType::Transfer { to_client, .. } => Some(to_client),
+                    TransactionType::Dispute { .. } | TransactionType::Resolve { .. } | TransactionType::ChargeBack { .. } => {
+                        cross_shard.get(&transaction.tx).copied()
+                    },
+                    _ => None,
+                };
+
+                match counterparty.map(|client| (client, self.shard_of(client))) {
+                    Some((counterparty, other)) if other != shard => {
+                        let is_transfer = matches!(transaction.txn_type, TransactionType::Transfer { .. });
+                        let tx = transaction.tx;
+
+                        match self.apply_across(&senders, &engines, &mut clock, shard, other, transaction) {
+                            Ok(_) if is_transfer => { cross_shard.insert(tx, counterparty); },
+                            Ok(_) => {},
+                            Err(e) => on_rejected(context, e),
+                        }
+                    },
+                    _ => {
+                        if clock.catch_up(&senders[shard], shard).is_err()
+                            || senders[shard].send(Message::Apply(context, transaction)).is_err()
+                        {
+                            // the worker has gone away, which only happens if it panicked
+                            break;
+                        }
+                    },
+                }
+            }
+
+            // every shard ends at the same time, then closing the channels
+            // lets the workers finish
+            for (shard, sender) in senders.iter().enumerate() {
+                let _ = clock.catch_up(sender, shard);
+            }
+            drop(senders);
+
+            workers.into_iter()
+                .map(|worker| worker.join())
+                .collect::<Result<Vec<()>, _>>()
+        }).map_err(|_| "A shard worker panicked")?;
+
+        let mut combined = TransactionEngine::with_config(self.config.clone());
+        for engine in engines {
+            combined.absorb(engine.into_inner().map_err(|_| "Shard engine is unavailable")?);
+        }
+
+        Ok(combined)
+    }
+
+    /// waits for both shards to apply everything queued for them, then
+    /// applies the transaction across the engines of the two shards
+    fn apply_across<P>(&self, senders : &[mpsc::SyncSender<Message<P>>], engines : &[Mutex<TransactionEngine>],
+        clock : &mut Clock, shard : usize, other : usize, transaction : Transaction) -> crate::Result<()>
+    {
+        for index in [shard, other] {
+            clock.catch_up(&senders[index], index)?;
+            let (reply, done) = mpsc::sync_channel(1);
+            senders[index].send(Message::Sync(reply)).map_err(|_| "Shard worker has stopped")?;
+            done.recv().map_err(|_| "Shard worker has stopped")?;
+        }
+
+        let mut engine = engines[shard].lock().map_err(|_| "Shard engine is unavailable")?;
+        let mut other_engine = engines[other].lock().map_err(|_| "Shard engine is unavailable")?;
+        engine.apply_across(&mut other_engine, transaction)
+    }
+}
+
+/// `Clock` is the time seen by the reader, kept as the last timestamp of each
+/// day, along with how far each shard has been advanced through it
+struct Clock {
+    days : Vec<Timestamp>,
+    advanced : Vec<Option<Timestamp>>,
+}
+
+impl Clock {
+    fn new(shards : usize) -> Self {
+        Clock {
+            days : Vec::new(),
+            advanced : vec![None; shards],
+        }
+    }
+
+    /// moves the clock forward to the timestamp, if it is later
+    fn see(&mut self, timestamp : Timestamp) {
+        match self.days.last_mut() {
+            Some(last) if *last >= timestamp => {},
+            Some(last) if *last / DAY == timestamp / DAY => *last = timestamp,
+            _ => self.days.push(timestamp),
+        }
+    }
+
+    /// sends the shard the timestamps it has not been advanced through yet
+    fn catch_up<P>(&mut self, sender : &mpsc::SyncSender<Message<P>>, shard : usize) -> crate::Result<()> {
+        let advanced = self.advanced[shard];
+        let start = self.days.partition_point(|timestamp| Some(*timestamp) <= advanced);
+        if start == self.days.len() {
+            return Ok(());
+        }
+
+        sender.send(Message::Advance(self.days[start..].to_vec())).map_err(|_| "Shard worker has stopped")?;
+        self.advanced[shard] = self.days.last().copied();
+        Ok(())
+    }
+}
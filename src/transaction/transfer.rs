@@ -0,0 +1,99 @@
+//! Transfers between two clients.
+//!
+//! A transfer debits one client and credits another. Both sides are checked
+//! before any balance is touched, so either both legs are applied or none.
+//!
+//! A transfer can only be disputed by its sender and is disputed as a unit:
+//!
+//! |Step|Sender|Receiver|
+//! |-|-|-|
+//! |transfer|available - amount|available + amount|
+//! |dispute| |available -> held|
+//! |resolve| |held -> available|
+//! |chargeback|available + amount, locked|held - amount|
+//!
+//! The chargeback fee, if any, is charged to the sender. When either client
+//! is locked, the transaction is only applied if the lock policy allows it.
+//! Both legs are in the currency of the transfer. A transfer counts towards
+//! the withdrawal limits of the sender, like a withdrawal.
+use super::{Transaction, TransactionType};
+use super::amount::Amount;
+use super::config::{Debit, EngineConfig};
+use super::fees::FeeSource;
+use super::ledger::{ClientLedger, RecordKind, TransactionRecord};
+
+/// `apply` applies a transfer, or a dispute / resolve / chargeback of a
+/// transfer, onto the ledgers of the sender and the receiver
+pub(crate) fn apply(sender : &mut ClientLedger, receiver : &mut ClientLedger, transaction : &Transaction,
+    config : &EngineConfig) -> crate::Result<()>
+{
+    sender.check_lock(&transaction.txn_type, config)?;
+    receiver.check_lock(&transaction.txn_type, config)?;
+
+    match &transaction.txn_type {
+        TransactionType::Transfer { amount, .. } => transfer(sender, receiver, transaction, *amount, config),
+        TransactionType::Dispute { .. } => {
+            let record = sent(sender, transaction)?;
+            let (amount, currency) = (record.to_dispute(transaction)?, record.currency);
+            receiver.check_debit(amount, currency, Debit::Imposed, config)?;
+            receiver.balance_in_mut(currency).dispute(amount)?;
+            update(sender, receiver, transaction, |record| record.dispute(amount));
+            Ok(())
+        },
+        TransactionType::Resolve { .. } => {
+            let record = sent(sender, transaction)?;
+            let (amount, currency) = (record.to_release(transaction)?, record.currency);
+            receiver.balance_in_mut(currency).resolve(amount)?;
+            update(sender, receiver, transaction, |record| record.resolve(amount));
+            Ok(())
+        },
+        TransactionType::ChargeBack { .. } => {
+            let record = sent(sender, transaction)?;
+            let (amount, currency) = (record.to_release(transaction)?, record.currency);
+            receiver.balance_in_mut(currency).remove_held(amount)?;
+            sender.balance_in_mut(currency).chargeback_credit(amount)?;
+            sender.sync_lock();
+            sender.charge_fee(transaction.tx, FeeSource::ChargeBack, config.fees.chargeback_in(currency), amount, currency, config);
+            update(sender, receiver, transaction, |record| record.charge_back(amount));
+            Ok(())
+        },
+        _ => Err(format!("Transaction {} is not a transfer", transaction.tx).into()),
+    }
+}
+
+fn transfer(sender : &mut ClientLedger, receiver : &mut ClientLedger, transaction : &Transaction, amount : Amount,
+    config : &EngineConfig) -> crate::Result<()>
+{
+    if sender.get_record(transaction.tx).is_some() || receiver.get_record(transaction.tx).is_some() {
+        return Err(format!("Transaction {} has already been applied", transaction.tx).into());
+    }
+
+    let currency = transaction.currency;
+    sender.check_limits(amount, currency, transaction.timestamp, config)?;
+    sender.check_debit(amount, currency, Debit::Requested, config)?;
+    sender.balance_in_mut(currency).debit(amount);
+    receiver.balance_in_mut(currency).deposit(amount)?;
+    sender.count_withdrawn(amount, currency, transaction.timestamp);
+
+    let receiver_id = receiver.get_balance().client();
+    let sender_id = sender.get_balance().client();
+    sender.record(transaction.tx, TransactionRecord::new(RecordKind::TransferOut { to : receiver_id }, amount).with_currency(currency));
+    receiver.record(transaction.tx, TransactionRecord::new(RecordKind::TransferIn { from : sender_id }, amount).with_currency(currency));
+
+    Ok(())
+}
+
+/// returns the sender's record of the transfer
+fn sent<'a>(sender : &'a ClientLedger, transaction : &Transaction) -> crate::Result<&'a TransactionRecord> {
+    sender.get_record(transaction.tx)
+        .ok_or(format!("Transfer {} not found", transaction.tx).into())
+}
+
+/// applies the same change to the records of both sides of the transfer
+fn update(sender : &mut ClientLedger, receiver : &mut ClientLedger, transaction : &Transaction, change : impl Fn(&mut TransactionRecord)) {
+    for ledger in [sender, receiver] {
+        if let Some(record) = ledger.get_record_mut(transaction.tx) {
+            change(record);
+        }
+    }
+}
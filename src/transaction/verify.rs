@@ -0,0 +1,159 @@
+//! Ledger invariants.
+//!
+//! `verify` rebuilds the balances of a client from what its ledger remembers
+//! and checks them against the actual balances, in each currency:
+//!
+//! |Invariant|Holds when|
+//! |-|-|
+//! |`NegativeHeld`|held is not below zero|
+//! |`OpenDisputes`|held is the sum of the open disputes and holds|
+//! |`NetFlow`|total is the sum of deposits, transfers in, adjustments, interest and conversions in, less withdrawals, transfers out, chargebacks, fees and conversions out|
+//!
+//! As the net flow of every client is checked, the sum of all balances also
+//! adds up to the net flow of all clients. Amounts are compared to 4 decimals
+//! like everywhere else, so a balance that has only drifted by rounding is
+//! not reported. There is no invariant on the total itself, as it is not
+//! stored but always derived as available + held.
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::ClientId;
+use super::amount::Amount;
+use super::config::{EngineConfig, WithdrawalDisputes};
+use super::currency::Currency;
+use super::ledger::{ClientLedger, RecordKind};
+
+/// `Invariant` is a rule that the balances of a client should follow
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Invariant {
+    NegativeHeld,
+    OpenDisputes,
+    NetFlow,
+}
+
+impl Invariant {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Invariant::NegativeHeld => "negative_held",
+            Invariant::OpenDisputes => "open_disputes",
+            Invariant::NetFlow => "net_flow",
+        }
+    }
+}
+
+/// `Violation` is an invariant that a balance does not follow, with the
+/// amount it should have been and the amount it is
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub client : ClientId,
+    pub currency : Currency,
+    pub invariant : Invariant,
+    pub expected : Amount,
+    pub actual : Amount,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        let what = match self.invariant {
+            Invariant::NegativeHeld | Invariant::OpenDisputes => "held",
+            Invariant::NetFlow => "total by net flow",
+        };
+        write!(f, "Client {} {}", self.client, what)?;
+        if !self.currency.is_default() {
+            write!(f, " in {}", self.currency)?;
+        }
+        write!(f, " should be {} but is {}", self.expected, self.actual)
+    }
+}
+
+/// what the balance in a currency should be, going by the ledger
+#[derive(Debug)]
+struct Expected {
+    total : Amount,
+    held : Amount,
+}
+
+impl Expected {
+    fn new() -> Self {
+        Expected { total : Amount::new(0.0), held : Amount::new(0.0) }
+    }
+}
+
+/// the expected balance in a currency, which may not have a balance yet
+fn expect(expected : &mut BTreeMap<Currency, Expected>, currency : Currency) -> &mut Expected {
+    expected.entry(currency).or_insert_with(Expected::new)
+}
+
+/// `verify` checks the balances of a client against its ledger and returns
+/// the invariants they do not follow, by currency
+pub fn verify(ledger : &ClientLedger, config : &EngineConfig) -> Vec<Violation> {
+    let client = ledger.get_balance().client();
+    let provisional = config.withdrawal_disputes == WithdrawalDisputes::ProvisionalCredit;
+
+    let mut expected : BTreeMap<Currency, Expected> = BTreeMap::new();
+    for balance in ledger.balances() {
+        expected.insert(balance.currency(), Expected::new());
+    }
+
+    for (_, record) in ledger.records() {
+        let balance = expect(&mut expected, record.currency);
+        match record.kind {
+            RecordKind::Deposit | RecordKind::TransferIn { .. } => {
+                balance.total += record.amount - record.charged_back;
+                balance.held += record.disputed;
+            },
+            RecordKind::Withdrawal if provisional => {
+                // the dispute is credited to held and a chargeback gives it back
+                balance.total += record.disputed + record.charged_back - record.amount;
+                balance.held += record.disputed;
+            },
+            RecordKind::Withdrawal => {
+                balance.total -= record.amount + record.charged_back;
+                balance.held += record.disputed;
+            },
+            RecordKind::TransferOut { .. } => {
+                // the receiver holds the dispute, the sender gets the chargeback
+                balance.total += record.charged_back - record.amount;
+            },
+        }
+    }
+    for fee in ledger.fees() {
+        expect(&mut expected, fee.currency).total -= fee.amount;
+    }
+    for adjustment in ledger.adjustments() {
+        expect(&mut expected, adjustment.currency).total += adjustment.amount;
+    }
+    for interest in ledger.interest() {
+        expect(&mut expected, interest.currency).total += interest.amount;
+    }
+    for conversion in ledger.conversions() {
+        expect(&mut expected, conversion.from).total -= conversion.amount;
+        expect(&mut expected, conversion.to).total += conversion.converted;
+    }
+    for hold in ledger.holds() {
+        expect(&mut expected, hold.currency).held += hold.amount;
+    }
+
+    let mut violations = Vec::new();
+    let zero = Amount::new(0.0);
+    for (currency, expected) in expected {
+        let (held, total) = ledger.balance_in(currency)
+            .map(|balance| (balance.held(), balance.total()))
+            .unwrap_or((zero, zero));
+
+        let mut violation = |invariant, expected, actual| {
+            violations.push(Violation { client, currency, invariant, expected, actual });
+        };
+        if *held < 0.0 && held != zero {
+            violation(Invariant::NegativeHeld, zero, held);
+        }
+        if held != expected.held {
+            violation(Invariant::OpenDisputes, expected.held, held);
+        }
+        if total != expected.total {
+            violation(Invariant::NetFlow, expected.total, total);
+        }
+    }
+
+    violations
+}
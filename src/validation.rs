@@ -0,0 +1,88 @@
+//! `txengine::validation::Validation`
+//!
+//! Applies the inputs to an engine only to count what happens to each row,
+//! so that a partner file can be checked before it is processed for real.
+//! Every row ends up as one of:
+//!
+//! |Outcome|Row|
+//! |-|-|
+//! |`applied`|was applied|
+//! |`rejected`|was parsed but the engine rejected it, counted by the kind of error|
+//! |`unparsed`|could not be parsed and was skipped|
+//!
+//! Rejections that are not a `LedgerError`, e.g. a dispute of a transaction
+//! that has nothing left to dispute, are of the kind `other`.
+use std::collections::BTreeMap;
+
+use crate::readers::{InputOrder, MultiFileReader, Provenance};
+use crate::transaction::TransactionEngine;
+use crate::transaction::ledger::LedgerError;
+
+/// `Validation` counts the outcome of each row of the inputs
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Validation {
+    pub applied : usize,
+    pub unparsed : usize,
+    /// rejected rows by the kind of error
+    pub rejected : BTreeMap<&'static str, usize>,
+}
+
+impl Validation {
+    pub fn new() -> Self {
+        Validation::default()
+    }
+
+    /// `run` applies all transactions of the reader onto the engine, which
+    /// is expected to be thrown away afterwards. `on_error` is called for
+    /// each rejected transaction
+    pub fn run<F>(engine : &mut TransactionEngine, reader : MultiFileReader, order : InputOrder, mut on_error : F)
+        -> crate::Result<Self>
+        where
+            F : FnMut(&Provenance, &crate::Error)
+    {
+        let parse_errors = reader.parse_errors();
+        let mut validation = Validation::new();
+
+        for t in reader.iter(order)? {
+            let result = engine.apply(t.transaction);
+            if let Err(e) = &result {
+                on_error(&t.provenance, e);
+            }
+            validation.record(&result);
+        }
+
+        validation.unparsed = parse_errors.count();
+        Ok(validation)
+    }
+
+    /// counts the outcome of applying a row
+    pub fn record(&mut self, result : &crate::Result<()>) {
+        match result {
+            Ok(()) => self.applied += 1,
+            Err(e) => *self.rejected.entry(kind_of(e)).or_default() += 1,
+        }
+    }
+
+    /// all rows, whether or not they were applied
+    pub fn rows(&self) -> usize {
+        self.applied + self.unparsed + self.rejected.values().sum::<usize>()
+    }
+
+    /// rows that were rejected or could not be parsed
+    pub fn failed(&self) -> usize {
+        self.rows() - self.applied
+    }
+
+    /// the percentage of rows that failed, 0 when there are no rows
+    pub fn reject_rate(&self) -> f64 {
+        match self.rows() {
+            0 => 0.0,
+            rows => self.failed() as f64 * 100.0 / rows as f64,
+        }
+    }
+}
+
+/// the kind of a rejection, see `LedgerError::kind`
+fn kind_of(e : &crate::Error) -> &'static str {
+    e.downcast_ref::<LedgerError>().map(LedgerError::kind).unwrap_or("other")
+}
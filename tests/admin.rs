@@ -0,0 +1,32 @@
+use txnengine::transaction::{Origin, TransactionEngine, Transaction, TransactionType};
+use txnengine::transaction::amount::Amount;
+
+#[test]
+fn operator_unlocks_and_adjusts() -> txnengine::Result<()> {
+    let mut engine = TransactionEngine::new();
+    engine.apply(Transaction::new(1, 1, TransactionType::Deposit { amount: Amount::new(10.0) }))?;
+    engine.apply(Transaction::new(1, 2, TransactionType::Deposit { amount: Amount::new(5.0) }))?;
+    engine.apply(Transaction::new(1, 2, TransactionType::Dispute { amount: None }))?;
+    engine.apply(Transaction::new(1, 2, TransactionType::ChargeBack { amount: None }))?;
+    assert!(engine.apply(Transaction::new(1, 3, TransactionType::Deposit { amount: Amount::new(1.0) })).is_err());
+
+    // partners cannot unlock or adjust
+    assert!(engine.apply(Transaction::new(1, 4, TransactionType::Unlock)).is_err());
+    let adjustment = TransactionType::Adjustment { amount: Amount::new(-2.5), reason: String::from("duplicate credit") };
+    assert!(engine.apply(Transaction::new(1, 5, adjustment)).is_err());
+
+    engine.apply(Transaction::new(1, 4, TransactionType::Unlock).with_origin(Origin::Operator))?;
+    engine.apply(Transaction::new(1, 3, TransactionType::Deposit { amount: Amount::new(1.0) }))?;
+
+    let adjustment = TransactionType::Adjustment { amount: Amount::new(-2.5), reason: String::from("duplicate credit") };
+    engine.apply(Transaction::new(1, 5, adjustment).with_origin(Origin::Operator))?;
+    engine.apply(Transaction::new(1, 6, TransactionType::Lock).with_origin(Origin::Operator))?;
+
+    let ledger = engine.get_ledger(1).ok_or("Ledger not found")?;
+    assert_eq!(ledger.get_balance().available(), 8.5);
+    assert!(ledger.get_balance().locked());
+    assert_eq!(ledger.adjustments().len(), 1);
+    assert_eq!(ledger.adjustments()[0].reason, "duplicate credit");
+
+    Ok(())
+}
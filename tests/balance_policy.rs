@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+
+use txnengine::transaction::{TransactionEngine, Transaction, TransactionType};
+use txnengine::transaction::amount::Amount;
+use txnengine::transaction::config::{BalancePolicy, EngineConfig};
+use txnengine::transaction::fees::{Fee, FeeRule, FeeSchedule};
+
+fn spent_deposit(policy : BalancePolicy) -> txnengine::Result<TransactionEngine> {
+    let fees = FeeSchedule { chargeback : Some(Fee::new(FeeRule::Flat(Amount::new(5.0)))), ..FeeSchedule::default() };
+    let mut engine = TransactionEngine::with_config(EngineConfig::default().with_balance_policy(policy).with_fees(fees));
+    engine.apply(Transaction::new(1, 1, TransactionType::Deposit { amount: Amount::new(10.0) }))?;
+    engine.apply(Transaction::new(1, 2, TransactionType::Withdrawal { amount: Amount::new(8.0) }))?;
+    Ok(engine)
+}
+
+#[test]
+fn strict_and_disputes_only() -> txnengine::Result<()> {
+    let mut engine = spent_deposit(BalancePolicy::Strict)?;
+    assert!(engine.apply(Transaction::new(1, 1, TransactionType::Dispute { amount: None })).is_err());
+
+    let mut engine = spent_deposit(BalancePolicy::NegativeFromDisputes)?;
+    assert!(engine.apply(Transaction::new(1, 3, TransactionType::Withdrawal { amount: Amount::new(3.0) })).is_err());
+    engine.apply(Transaction::new(1, 1, TransactionType::Dispute { amount: None }))?;
+    engine.apply(Transaction::new(1, 1, TransactionType::ChargeBack { amount: None }))?;
+
+    let balance = engine.get_ledger(1).ok_or("Ledger not found")?.get_balance();
+    assert_eq!(balance.available(), -13.0);
+    Ok(())
+}
+
+#[test]
+fn overdraft_up_to_client_limit() -> txnengine::Result<()> {
+    let limits = HashMap::from([(1, Amount::new(10.0))]);
+    let mut engine = spent_deposit(BalancePolicy::Overdraft(limits))?;
+
+    engine.apply(Transaction::new(1, 3, TransactionType::Withdrawal { amount: Amount::new(9.0) }))?;
+    assert!(engine.apply(Transaction::new(1, 4, TransactionType::Withdrawal { amount: Amount::new(7.0) })).is_err());
+    assert!(engine.apply(Transaction::new(1, 1, TransactionType::Dispute { amount: None })).is_err());
+
+    engine.apply(Transaction::new(1, 5, TransactionType::Deposit { amount: Amount::new(7.0) }))?;
+    engine.apply(Transaction::new(1, 5, TransactionType::Dispute { amount: None }))?;
+    engine.apply(Transaction::new(1, 5, TransactionType::ChargeBack { amount: None }))?;
+
+    // the chargeback fee is capped at the limit
+    let ledger = engine.get_ledger(1).ok_or("Ledger not found")?;
+    assert_eq!(ledger.get_balance().available(), -10.0);
+    assert_eq!(ledger.fees()[0].amount, 3.0);
+
+    // clients without a limit cannot go below zero
+    engine.apply(Transaction::new(2, 6, TransactionType::Deposit { amount: Amount::new(1.0) }))?;
+    assert!(engine.apply(Transaction::new(2, 7, TransactionType::Withdrawal { amount: Amount::new(2.0) })).is_err());
+    Ok(())
+}
@@ -0,0 +1,114 @@
+use std::sync::{Arc, Mutex};
+
+use txnengine::balances;
+use txnengine::transaction::{TransactionEngine, Transaction, TransactionType};
+use txnengine::transaction::amount::Amount;
+use txnengine::transaction::batch::BatchError;
+use txnengine::transaction::config::EngineConfig;
+use txnengine::transaction::interest::{InterestRule, InterestSchedule, DAY};
+use txnengine::transaction::ledger::{ClientBalance, LedgerError};
+use txnengine::transaction::observer::EngineObserver;
+use txnengine::transaction::screening::{Action, DisputeCount, Rules};
+
+fn deposit(client : u16, tx : u32, amount : f32) -> Transaction {
+    Transaction::new(client, tx, TransactionType::Deposit { amount: Amount::new(amount) })
+}
+
+fn withdrawal(client : u16, tx : u32, amount : f32) -> Transaction {
+    Transaction::new(client, tx, TransactionType::Withdrawal { amount: Amount::new(amount) })
+}
+
+/// records the transactions that observers are told about
+#[derive(Debug, Clone, Default)]
+struct Recorder {
+    events : Arc<Mutex<Vec<String>>>,
+}
+
+impl EngineObserver for Recorder {
+    fn on_applied(&mut self, transaction : &Transaction, _before : &ClientBalance, _after : &ClientBalance) {
+        self.events.lock().unwrap().push(format!("applied {}", transaction.tx));
+    }
+
+    fn on_rejected(&mut self, transaction : &Transaction, _error : &txnengine::Error) {
+        self.events.lock().unwrap().push(format!("rejected {}", transaction.tx));
+    }
+}
+
+#[test]
+fn all_or_nothing() -> txnengine::Result<()> {
+    let recorder = Recorder::default();
+    let mut engine = TransactionEngine::new().with_observer(Box::new(recorder.clone()));
+    engine.apply_batch(vec![deposit(1, 1, 10.0), deposit(2, 2, 5.0)])?;
+    let before = balances::from_engine(&engine);
+
+    let error = engine.apply_batch(vec![
+        withdrawal(1, 3, 4.0),
+        Transaction::new(1, 4, TransactionType::Transfer { to_client: 3, amount: Amount::new(2.0) }),
+        Transaction::new(1, 1, TransactionType::Dispute { amount: None }),
+        withdrawal(2, 5, 6.0),
+        deposit(2, 6, 1.0),
+    ]).err().ok_or("Batch should have failed")?;
+
+    let error = error.downcast_ref::<BatchError>().ok_or("Not a batch error")?;
+    assert_eq!((error.index, error.tx), (3, 5));
+    assert!(matches!(error.error.downcast_ref::<LedgerError>(), Some(LedgerError::InsufficentFunds { .. })));
+
+    // nothing of the batch is left, not even the client it created
+    assert_eq!(balances::from_engine(&engine), before);
+    assert!(engine.get_ledger(3).is_none());
+    assert!(engine.get_ledger(1).ok_or("Ledger not found")?.get_record(3).is_none());
+    assert_eq!(*recorder.events.lock().unwrap(), vec!["applied 1", "applied 2", "rejected 5"]);
+
+    // the same transactions apply once the failing one is left out
+    engine.apply_batch(vec![withdrawal(1, 3, 4.0), withdrawal(2, 5, 1.0)])?;
+    assert_eq!(engine.get_ledger(1).ok_or("Ledger not found")?.get_balance().available(), 6.0);
+    Ok(())
+}
+
+#[test]
+fn roll_back_clock_and_holds() -> txnengine::Result<()> {
+    let config = EngineConfig::default().with_interest(InterestSchedule::new(InterestRule::Flat(36.5)).with_period_days(1)?);
+    let mut engine = TransactionEngine::with_config(config.clone());
+    let mut expected = TransactionEngine::with_config(config);
+    for engine in [&mut engine, &mut expected] {
+        engine.apply(deposit(1, 1, 1000.0).with_timestamp(0))?;
+        engine.apply(Transaction::new(1, 2, TransactionType::Hold { amount: Amount::new(100.0), expires: Some(DAY) }))?;
+    }
+
+    // moves the clock past the expiry of the hold and a posting of interest
+    assert!(engine.apply_batch(vec![
+        deposit(2, 3, 1.0).with_timestamp(2 * DAY),
+        Transaction::new(2, 4, TransactionType::Hold { amount: Amount::new(1.0), expires: Some(3 * DAY) }),
+        withdrawal(1, 5, 5000.0),
+    ]).is_err());
+
+    assert_eq!(engine.clock(), Some(0));
+    let ledger = engine.get_ledger(1).ok_or("Ledger not found")?;
+    assert_eq!((ledger.get_balance().held(), ledger.interest().len()), (Amount::new(100.0), 0));
+
+    // the hold still expires and interest is still posted, only once
+    for engine in [&mut engine, &mut expected] {
+        engine.apply(deposit(1, 6, 1.0).with_timestamp(4 * DAY))?;
+    }
+    assert_eq!(balances::from_engine(&engine), balances::from_engine(&expected));
+    assert_eq!(engine.get_ledger(1).ok_or("Ledger not found")?.interest().len(), 4);
+    assert!(engine.verify().is_empty());
+    Ok(())
+}
+
+#[test]
+fn screening_rolled_back() -> txnengine::Result<()> {
+    let rules = Rules::new().with_rule(DisputeCount::new(1, Action::Reject));
+    let mut engine = TransactionEngine::new().with_screening(Box::new(rules));
+    engine.apply(deposit(1, 1, 5.0))?;
+    engine.apply(deposit(1, 2, 5.0))?;
+
+    let dispute = |tx| Transaction::new(1, tx, TransactionType::Dispute { amount: None });
+    assert!(engine.apply_batch(vec![dispute(1), withdrawal(1, 3, 50.0)]).is_err());
+
+    // the rolled back dispute does not count, so this is the first one
+    engine.apply(dispute(2))?;
+    assert_eq!(engine.get_ledger(1).ok_or("Ledger not found")?.get_balance().held(), 5.0);
+    assert!(engine.apply(dispute(1)).is_err());
+    Ok(())
+}
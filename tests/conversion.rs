@@ -0,0 +1,44 @@
+use std::fs;
+
+use txnengine::transaction::{TransactionEngine, Transaction, TransactionType};
+use txnengine::transaction::amount::Amount;
+use txnengine::transaction::config::EngineConfig;
+use txnengine::transaction::currency::Currency;
+use txnengine::transaction::rates::RateTable;
+
+#[test]
+fn convert_at_effective_rate() -> txnengine::Result<()> {
+    let path = std::env::temp_dir().join(format!("txnengine-rates-{}.csv", std::process::id()));
+    fs::write(&path, "from,to,rate,effective\n\
+        EUR,JPY,160.5,100\n\
+        EUR,JPY,150.25,0\n\
+        EUR,USD,1.0845,\n")?;
+    let rates = RateTable::from_csv(path.to_str().ok_or("Invalid path")?)?;
+    fs::remove_file(&path)?;
+
+    let (eur, jpy, usd) : (Currency, Currency, Currency) = ("EUR".parse()?, "JPY".parse()?, "USD".parse()?);
+    let mut engine = TransactionEngine::with_config(EngineConfig::default().with_rates(rates));
+    engine.apply(Transaction::new(1, 1, TransactionType::Deposit { amount: Amount::new(100.0) }).with_currency(eur))?;
+
+    // before the second rate, then at the clock which has moved past it
+    engine.apply(Transaction::new(1, 2, TransactionType::Convert { amount: Amount::new(10.01), to: jpy }).with_currency(eur).with_timestamp(50))?;
+    engine.apply(Transaction::new(1, 3, TransactionType::Deposit { amount: Amount::new(1.0) }).with_currency(eur).with_timestamp(120))?;
+    engine.apply(Transaction::new(1, 4, TransactionType::Convert { amount: Amount::new(1.0), to: jpy }).with_currency(eur))?;
+    engine.apply(serde_json::from_str(r#"{"type": "convert", "client": 1, "tx": 5, "amount": 10.0, "currency": "EUR", "to_currency": "USD"}"#)?)?;
+
+    let ledger = engine.get_ledger(1).ok_or("Ledger not found")?;
+    assert_eq!(ledger.balance_in(eur).ok_or("No EUR balance")?.available(), 79.99);
+    assert_eq!(ledger.balance_in(jpy).ok_or("No JPY balance")?.available(), 1665.0);
+    assert_eq!(ledger.balance_in(usd).ok_or("No USD balance")?.available(), 10.85);
+
+    let conversions = ledger.conversions();
+    assert_eq!(conversions.len(), 3);
+    assert_eq!((conversions[0].rate, conversions[0].effective, conversions[0].converted), (150.25, 0, Amount::new(1504.0)));
+    assert_eq!((conversions[1].rate, conversions[1].effective), (160.5, 100));
+
+    // no rate back from JPY, and not more than the available funds
+    assert!(engine.apply(Transaction::new(1, 6, TransactionType::Convert { amount: Amount::new(100.0), to: eur }).with_currency(jpy)).is_err());
+    assert!(engine.apply(Transaction::new(1, 7, TransactionType::Convert { amount: Amount::new(80.0), to: usd }).with_currency(eur)).is_err());
+    assert!(serde_json::from_str::<Transaction>(r#"{"type": "convert", "client": 1, "tx": 8, "amount": 1.0}"#).is_err());
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use txnengine::transaction::{TransactionEngine, Transaction, TransactionType};
+use txnengine::transaction::amount::Amount;
+use txnengine::transaction::currency::Currency;
+use txnengine::transaction::ledger::WithoutCurrency;
+
+#[test]
+fn balance_per_currency() -> txnengine::Result<()> {
+    let data = "type,client,tx,amount,currency
+deposit,1,1,10.0,
+deposit,1,2,5.129,eur
+deposit,1,3,1000.6,JPY
+withdrawal,1,4,2.0,EUR
+withdrawal,1,5,20.0,EUR
+dispute,1,3,,";
+
+    let mut engine = TransactionEngine::new();
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(data.as_bytes());
+    for transaction in reader.deserialize::<Transaction>() {
+        let _ = engine.apply(transaction?);
+    }
+
+    let eur : Currency = "EUR".parse()?;
+    let ledger = engine.get_ledger(1).ok_or("Ledger not found")?;
+    assert_eq!(ledger.get_balance().available(), 10.0);
+    assert_eq!(ledger.balance_in(eur).ok_or("No EUR balance")?.available(), 3.13);
+    assert_eq!(ledger.balance_in("JPY".parse()?).ok_or("No JPY balance")?.held(), 1001.0);
+    assert!(ledger.balance_in("USD".parse()?).is_none());
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for balance in ledger.balances() {
+        writer.serialize(balance)?;
+    }
+    let output = String::from_utf8(writer.into_inner()?)?;
+    assert_eq!(output, "client,currency,available,held,total,locked
+1,,10.0000,0.0000,10.0000,false
+1,EUR,3.13,0.00,3.13,false
+1,JPY,0,1001,1001,false
+");
+    assert_eq!(engine.iter().count(), 3);
+
+    // a balance in the default currency can be written without the currency
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.serialize(WithoutCurrency(ledger.get_balance()))?;
+    let output = String::from_utf8(writer.into_inner()?)?;
+    assert_eq!(output, "client,available,held,total,locked\n1,10.0000,0.0000,10.0000,false\n");
+
+    assert!("EURO".parse::<Currency>().is_err());
+    Ok(())
+}
+
+#[test]
+fn chargeback_locks_all_currencies() -> txnengine::Result<()> {
+    let eur : Currency = "EUR".parse()?;
+    let mut engine = TransactionEngine::new();
+    engine.apply(Transaction::new(1, 1, TransactionType::Deposit { amount: Amount::new(10.0) }).with_currency(eur))?;
+    engine.apply(Transaction::new(1, 2, TransactionType::Deposit { amount: Amount::new(3.0) }))?;
+    engine.apply(Transaction::new(1, 1, TransactionType::Dispute { amount: None }))?;
+    engine.apply(Transaction::new(1, 1, TransactionType::ChargeBack { amount: None }))?;
+
+    let ledger = engine.get_ledger(1).ok_or("Ledger not found")?;
+    assert!(ledger.locked());
+    assert!(ledger.get_balance().locked());
+    assert_eq!(ledger.balance_in(eur).ok_or("No EUR balance")?.total(), 0.0);
+    assert!(engine.apply(Transaction::new(1, 3, TransactionType::Deposit { amount: Amount::new(1.0) })).is_err());
+
+    // a client that only uses another currency has no default balance row
+    engine.apply(Transaction::new(2, 4, TransactionType::Deposit { amount: Amount::new(1.0) }).with_currency(eur))?;
+    let ledger = engine.get_ledger(2).ok_or("Ledger not found")?;
+    assert_eq!(ledger.balances().count(), 1);
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use std::fs;
+
+use txnengine::balances::{self, Balances, Change, Summary};
+use txnengine::transaction::amount::Amount;
+use txnengine::transaction::currency::Currency;
+
+fn balances_file(name : &str, contents : &str) -> txnengine::Result<Balances> {
+    let path = std::env::temp_dir().join(format!("txnengine-diff-{}-{}.csv", name, std::process::id()));
+    fs::write(&path, contents)?;
+    let balances = balances::from_csv(path.to_str().ok_or("Invalid path")?);
+    fs::remove_file(&path)?;
+    balances
+}
+
+#[test]
+fn diff_balances() -> txnengine::Result<()> {
+    let before = balances_file("before", "client,currency,available,held,total,locked\n\
+        1,,10.0000,0.0000,10.0000,false\n\
+        2,,5.0000,1.0000,6.0000,false\n\
+        3,EUR,2.00,0.00,2.00,false\n\
+        4,,1.0000,0.0000,1.0000,false\n")?;
+    let after = balances_file("after", "client,currency,available,held,total,locked\n\
+        1,,10.0000,0.0000,10.0000,false\n\
+        2,,5.0000,0.0000,5.0000,true\n\
+        4,,1.0040,0.0000,1.0040,false\n\
+        5,EUR,3.50,0.50,4.00,false\n")?;
+
+    let deltas = balances::diff(&before, &after, 0.0);
+    let moved : Vec<(u16, Change, Amount, Amount)> = deltas.iter().map(|d| (d.client, d.change, d.held, d.total)).collect();
+    assert_eq!(moved, vec![
+        (2, Change::Changed, Amount::new(-1.0), Amount::new(-1.0)),
+        (3, Change::Removed, Amount::new(0.0), Amount::new(-2.0)),
+        (4, Change::Changed, Amount::new(0.0), Amount::new(0.004)),
+        (5, Change::Added, Amount::new(0.5), Amount::new(4.0)),
+    ]);
+
+    // client 4 only moved within the tolerance
+    let deltas = balances::diff(&before, &after, 0.01);
+    let summary = Summary::new(&before, &after, &deltas);
+    assert_eq!((summary.compared, summary.unchanged, summary.changed, summary.added, summary.removed), (5, 2, 1, 1, 1));
+    assert_eq!((summary.locked, summary.unlocked), (1, 0));
+
+    let eur : Currency = "EUR".parse()?;
+    assert_eq!(summary.net[&Currency::DEFAULT].total, Amount::new(-1.0));
+    assert_eq!(summary.net[&eur].total, Amount::new(2.0));
+    assert_eq!(summary.net[&eur].available, Amount::new(1.5));
+    Ok(())
+}
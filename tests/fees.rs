@@ -0,0 +1,95 @@
+use std::fs;
+
+use txnengine::transaction::{TransactionEngine, Transaction, TransactionType};
+use txnengine::transaction::amount::Amount;
+use txnengine::transaction::config::EngineConfig;
+use txnengine::transaction::currency::Currency;
+use txnengine::transaction::fees::{Fee, FeeRule, FeeSchedule, FeeSource};
+
+#[test]
+fn fees_charged_with_transactions() -> txnengine::Result<()> {
+    let fees = FeeSchedule {
+        withdrawal : Some(Fee::new(FeeRule::Percentage(1.0)).with_min(Amount::new(0.5)).with_max(Amount::new(2.0))),
+        chargeback : Some(Fee::new(FeeRule::Flat(Amount::new(15.0)))),
+        ..FeeSchedule::default()
+    };
+    let mut engine = TransactionEngine::with_config(EngineConfig::default().with_fees(fees));
+
+    engine.apply(Transaction::new(1, 1, TransactionType::Deposit { amount: Amount::new(500.0) }))?;
+    engine.apply(Transaction::new(1, 2, TransactionType::Withdrawal { amount: Amount::new(10.0) }))?;
+    engine.apply(Transaction::new(1, 3, TransactionType::Withdrawal { amount: Amount::new(300.0) }))?;
+    engine.apply(Transaction::new(1, 4, TransactionType::Fee { amount: Amount::new(1.25) }))?;
+
+    // the fee has to be available along with the withdrawal
+    assert!(engine.apply(Transaction::new(1, 5, TransactionType::Withdrawal { amount: Amount::new(186.0) })).is_err());
+
+    let ledger = engine.get_ledger(1).ok_or("Ledger not found")?;
+    assert_eq!(ledger.get_balance().available(), 500.0 - 10.5 - 302.0 - 1.25);
+
+    let fees : Vec<_> = ledger.fees().iter().map(|f| (f.tx, f.source, *f.amount)).collect();
+    assert_eq!(fees, vec![(2, FeeSource::Withdrawal, 0.5), (3, FeeSource::Withdrawal, 2.0), (4, FeeSource::Fee, 1.25)]);
+
+    engine.apply(Transaction::new(2, 6, TransactionType::Deposit { amount: Amount::new(10.0) }))?;
+    engine.apply(Transaction::new(2, 6, TransactionType::Dispute { amount: None }))?;
+    engine.apply(Transaction::new(2, 6, TransactionType::ChargeBack { amount: None }))?;
+
+    let balance = engine.get_ledger(2).ok_or("Ledger not found")?.get_balance();
+    assert_eq!(balance.available(), -15.0);
+    assert!(balance.locked());
+
+    Ok(())
+}
+
+#[test]
+fn tiered_schedule_from_csv() -> txnengine::Result<()> {
+    let path = std::env::temp_dir().join(format!("txnengine-fees-{}.csv", std::process::id()));
+    fs::write(&path, "applies_to,rule,value,from,min,max\n\
+        withdrawal,flat,1,0,,\n\
+        withdrawal,percentage,0.5,1000,,20\n\
+        chargeback,flat,15,,,\n")?;
+
+    let schedule = FeeSchedule::from_csv(path.to_str().ok_or("Invalid path")?)?;
+    fs::remove_file(&path)?;
+
+    let withdrawal = schedule.withdrawal.ok_or("Missing withdrawal fee")?;
+    assert_eq!(withdrawal.compute(Amount::new(999.0)), 1.0);
+    assert_eq!(withdrawal.compute(Amount::new(2000.0)), 10.0);
+    assert_eq!(withdrawal.compute(Amount::new(10000.0)), 20.0);
+
+    let chargeback = schedule.chargeback.ok_or("Missing chargeback fee")?;
+    assert_eq!(chargeback.compute(Amount::new(3.0)), 15.0);
+
+    Ok(())
+}
+
+#[test]
+fn schedule_caps_tiers_and_currencies() -> txnengine::Result<()> {
+    let path = std::env::temp_dir().join(format!("txnengine-fee-caps-{}.csv", std::process::id()));
+    fs::write(&path, "applies_to,rule,value,from,min,max,currency\n\
+        withdrawal,percentage,1,0,2,,\n\
+        withdrawal,percentage,0.1,1000,,8,\n\
+        chargeback,flat,1.5,,,,\n\
+        chargeback,flat,200,,,,JPY\n")?;
+    let schedule = FeeSchedule::from_csv(path.to_str().ok_or("Invalid path")?)?;
+
+    // the caps are those of the tier they are given on
+    let withdrawal = schedule.withdrawal_in(Currency::DEFAULT).ok_or("Missing withdrawal fee")?;
+    assert_eq!(withdrawal.compute(Amount::new(100.0)), 2.0);
+    assert_eq!(withdrawal.compute(Amount::new(999.0)), 9.99);
+    assert_eq!(withdrawal.compute(Amount::new(1000.0)), 1.0);
+    assert_eq!(withdrawal.compute(Amount::new(100000.0)), 8.0);
+
+    let jpy : Currency = "JPY".parse()?;
+    let eur : Currency = "EUR".parse()?;
+    assert_eq!(schedule.chargeback_in(jpy).ok_or("Missing JPY chargeback fee")?.compute(Amount::new(1.0)), 200.0);
+    assert_eq!(schedule.chargeback_in(eur).ok_or("Missing EUR chargeback fee")?.compute(Amount::new(1.0)), 1.5);
+    assert!(schedule.withdrawal_in(jpy).is_some());
+
+    fs::write(&path, "applies_to,rule,value,from,min,max\nwithdrawal,flat,-1,,,\n")?;
+    assert!(FeeSchedule::from_csv(path.to_str().ok_or("Invalid path")?).is_err());
+    fs::write(&path, "applies_to,rule,value,from,min,max\nwithdrawal,percentage,1,,,-5\n")?;
+    assert!(FeeSchedule::from_csv(path.to_str().ok_or("Invalid path")?).is_err());
+
+    fs::remove_file(&path)?;
+    Ok(())
+}
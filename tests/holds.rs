@@ -0,0 +1,81 @@
+use txnengine::transaction::{TransactionEngine, Transaction, TransactionType};
+use txnengine::transaction::amount::Amount;
+use txnengine::transaction::sharded::ShardedEngine;
+
+#[test]
+fn holds_are_released_or_expire() -> txnengine::Result<()> {
+    let mut engine = TransactionEngine::new();
+    engine.apply(Transaction::new(1, 1, TransactionType::Deposit { amount: Amount::new(10.0) }).with_timestamp(100))?;
+    engine.apply(Transaction::new(1, 2, TransactionType::Hold { amount: Amount::new(4.0), expires: Some(200) }).with_timestamp(110))?;
+    engine.apply(Transaction::new(1, 3, TransactionType::Hold { amount: Amount::new(3.0), expires: None }))?;
+    assert!(engine.apply(Transaction::new(1, 4, TransactionType::Hold { amount: Amount::new(1.0), expires: Some(50) })).is_err());
+    assert!(engine.apply(Transaction::new(1, 5, TransactionType::Withdrawal { amount: Amount::new(4.0) })).is_err());
+
+    engine.apply(Transaction::new(1, 3, TransactionType::Release))?;
+    {
+        let ledger = engine.get_ledger(1).ok_or("Ledger not found")?;
+        assert_eq!(ledger.get_balance().available(), 6.0);
+        assert_eq!(ledger.get_balance().held(), 4.0);
+        assert_eq!(ledger.holds().count(), 1);
+    }
+
+    // another client's transaction moves the clock past the expiry
+    engine.apply(Transaction::new(2, 6, TransactionType::Deposit { amount: Amount::new(1.0) }).with_timestamp(200))?;
+    let ledger = engine.get_ledger(1).ok_or("Ledger not found")?;
+    assert_eq!(ledger.get_balance().available(), 10.0);
+    assert_eq!(ledger.get_balance().held(), 0.0);
+    assert_eq!(ledger.holds().count(), 0);
+    assert_eq!(engine.clock(), Some(200));
+    Ok(())
+}
+
+#[test]
+fn released_hold_placed_again() -> txnengine::Result<()> {
+    let mut engine = TransactionEngine::new();
+    engine.apply(Transaction::new(1, 1, TransactionType::Deposit { amount: Amount::new(10.0) }).with_timestamp(100))?;
+    engine.apply(Transaction::new(1, 2, TransactionType::Hold { amount: Amount::new(4.0), expires: Some(200) }))?;
+    engine.apply(Transaction::new(1, 2, TransactionType::Release))?;
+
+    // the same tx held again, without an expiry and then with a later one
+    engine.apply(Transaction::new(1, 2, TransactionType::Hold { amount: Amount::new(3.0), expires: None }))?;
+    engine.apply(Transaction::new(1, 3, TransactionType::Deposit { amount: Amount::new(1.0) }).with_timestamp(250))?;
+    assert_eq!(engine.get_ledger(1).ok_or("Ledger not found")?.get_balance().held(), 3.0);
+
+    engine.apply(Transaction::new(1, 2, TransactionType::Release))?;
+    engine.apply(Transaction::new(1, 2, TransactionType::Hold { amount: Amount::new(2.0), expires: Some(400) }))?;
+    engine.apply(Transaction::new(1, 4, TransactionType::Deposit { amount: Amount::new(1.0) }).with_timestamp(300))?;
+    assert_eq!(engine.get_ledger(1).ok_or("Ledger not found")?.get_balance().held(), 2.0);
+
+    engine.apply(Transaction::new(1, 5, TransactionType::Deposit { amount: Amount::new(1.0) }).with_timestamp(400))?;
+    assert_eq!(engine.get_ledger(1).ok_or("Ledger not found")?.get_balance().held(), 0.0);
+    Ok(())
+}
+
+#[test]
+fn holds_expire_the_same_when_sharded() -> txnengine::Result<()> {
+    let transactions = || {
+        (0..200u32).map(|tx| {
+            let client = (tx % 7) as u16;
+            let txn_type = match tx % 4 {
+                0 => TransactionType::Deposit { amount: Amount::new(5.0) },
+                1 => TransactionType::Hold { amount: Amount::new(3.0), expires: Some(tx as u64 + 20) },
+                2 => TransactionType::Withdrawal { amount: Amount::new(4.0) },
+                _ => TransactionType::Deposit { amount: Amount::new(1.0) },
+            };
+            ((), Transaction::new(client, tx, txn_type).with_timestamp(tx as u64))
+        })
+    };
+
+    let mut single = TransactionEngine::new();
+    for (_, transaction) in transactions() {
+        let _ = single.apply(transaction);
+    }
+    let sharded = ShardedEngine::new(3).process(transactions(), |_, _| {})?;
+
+    for client in 0..7 {
+        let expected = single.get_ledger(client).ok_or("Ledger not found")?;
+        let actual = sharded.get_ledger(client).ok_or("Ledger not found")?;
+        assert_eq!(expected.get_balance(), actual.get_balance());
+    }
+    Ok(())
+}
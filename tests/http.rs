@@ -0,0 +1,95 @@
+#![cfg(feature = "http")]
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use txnengine::http::HttpServer;
+use txnengine::transaction::TransactionEngine;
+
+/// sends a request and returns the status code and body of the response
+fn request(addr : SocketAddr, method : &str, path : &str, body : &str) -> txnengine::Result<(u16, String)> {
+    send(addr, method, path, "", body)
+}
+
+/// submits a transaction with an idempotency key
+fn submit(addr : SocketAddr, key : &str, body : &str) -> txnengine::Result<(u16, String)> {
+    send(addr, "POST", "/transactions", &format!("Idempotency-Key: {}\r\n", key), body)
+}
+
+fn send(addr : SocketAddr, method : &str, path : &str, headers : &str, body : &str) -> txnengine::Result<(u16, String)> {
+    let mut stream = TcpStream::connect(addr)?;
+    write!(stream, "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n{}Content-Length: {}\r\n\r\n{}",
+        method, path, headers, body.len(), body)?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+
+    let status = response.split(' ').nth(1).ok_or("Missing status")?.parse()?;
+    let body = response.split("\r\n\r\n").nth(1).unwrap_or("").to_string();
+    Ok((status, body))
+}
+
+#[test]
+fn rest_api() -> txnengine::Result<()> {
+    let engine = Arc::new(Mutex::new(TransactionEngine::new()));
+    let server = HttpServer::bind("127.0.0.1:0", engine)?;
+    let addr = server.local_addr()?;
+    thread::spawn(move || server.run());
+
+    let deposit = r#"{"type":"deposit","client":1,"tx":1,"amount":"10.0"}"#;
+    let (status, body) = submit(addr, "a", deposit)?;
+    assert_eq!(status, 201);
+    assert!(body.contains(r#""available":"10.0000""#));
+
+    // resubmitting with the same key is not applied twice
+    assert_eq!(submit(addr, "a", deposit)?.0, 201);
+    let (status, body) = request(addr, "GET", "/clients/1", "")?;
+    assert_eq!(status, 200);
+    assert!(body.contains(r#""total":"10.0000""#));
+
+    let conflicting = r#"{"type":"deposit","client":1,"tx":1,"amount":"11.0"}"#;
+    assert_eq!(submit(addr, "a", conflicting)?.0, 409);
+
+    let overdraw = r#"{"type":"withdrawal","client":1,"tx":2,"amount":50}"#;
+    assert_eq!(submit(addr, "b", overdraw)?.0, 422);
+    assert_eq!(request(addr, "POST", "/transactions", "not json")?.0, 400);
+
+    // a rejected submission is not remembered, so it can be retried once funded
+    let funding = r#"{"type":"deposit","client":1,"tx":4,"amount":50}"#;
+    assert_eq!(request(addr, "POST", "/transactions", funding)?.0, 201);
+    assert_eq!(submit(addr, "b", overdraw)?.0, 201);
+
+    // without a key a deposit is still applied once for each tx
+    let (status, body) = request(addr, "POST", "/transactions", funding)?;
+    assert_eq!(status, 200);
+    assert!(body.contains(r#""total":"10.0000""#));
+    assert_eq!(request(addr, "POST", "/transactions", r#"{"type":"deposit","client":1,"tx":4,"amount":5}"#)?.0, 409);
+
+    // disputing again after a resolve is applied again
+    let dispute = r#"{"type":"dispute","client":1,"tx":4}"#;
+    assert_eq!(request(addr, "POST", "/transactions", dispute)?.0, 201);
+    assert_eq!(request(addr, "POST", "/transactions", r#"{"type":"resolve","client":1,"tx":4}"#)?.0, 201);
+    let (status, body) = request(addr, "POST", "/transactions", dispute)?;
+    assert_eq!(status, 201);
+    assert!(body.contains(r#""held":"50.0000""#));
+    assert_eq!(request(addr, "POST", "/transactions", r#"{"type":"resolve","client":1,"tx":4}"#)?.0, 201);
+
+    assert_eq!(request(addr, "POST", "/transactions", r#"{"type":"dispute","client":1,"tx":1}"#)?.0, 201);
+    assert_eq!(request(addr, "POST", "/transactions", r#"{"type":"chargeback","client":1,"tx":1}"#)?.0, 201);
+    let locked = r#"{"type":"deposit","client":1,"tx":3,"amount":1}"#;
+    assert_eq!(request(addr, "POST", "/transactions", locked)?.0, 423);
+
+    let (status, body) = request(addr, "GET", "/transactions/1", "")?;
+    assert_eq!(status, 200);
+    assert!(body.contains(r#""client":1"#));
+
+    assert_eq!(request(addr, "GET", "/transactions/9", "")?.0, 404);
+    assert_eq!(request(addr, "GET", "/clients/2", "")?.0, 404);
+    assert_eq!(request(addr, "GET", "/clients", "")?.0, 200);
+    assert_eq!(request(addr, "GET", "/openapi.json", "")?.0, 200);
+    assert_eq!(request(addr, "DELETE", "/clients", "")?.0, 405);
+
+    Ok(())
+}
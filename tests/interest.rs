@@ -0,0 +1,63 @@
+use txnengine::transaction::{TransactionEngine, Transaction, TransactionType};
+use txnengine::transaction::amount::Amount;
+use txnengine::transaction::config::EngineConfig;
+use txnengine::transaction::interest::{InterestRule, InterestSchedule, DAY};
+use txnengine::transaction::sharded::ShardedEngine;
+
+/// 0.1% a day, posted every 10 days
+fn schedule() -> txnengine::Result<InterestSchedule> {
+    InterestSchedule::new(InterestRule::Flat(36.5)).with_period_days(10)
+}
+
+#[test]
+fn accrue_and_post() -> txnengine::Result<()> {
+    let mut engine = TransactionEngine::with_config(EngineConfig::default().with_interest(schedule()?));
+    engine.apply(Transaction::new(1, 1, TransactionType::Deposit { amount: Amount::new(1000.0) }).with_timestamp(0))?;
+    engine.apply(Transaction::new(1, 2, TransactionType::Deposit { amount: Amount::new(1000.0) }).with_timestamp(5 * DAY + 60))?;
+    engine.apply(Transaction::new(2, 3, TransactionType::Deposit { amount: Amount::new(10.0) }).with_timestamp(12 * DAY))?;
+
+    // 5 days on 1000 and 5 days on 2000, then 2 days on 2015 not posted yet
+    let ledger = engine.get_ledger(1).ok_or("Ledger not found")?;
+    assert_eq!(ledger.get_balance().available(), 2015.0);
+
+    let posted = ledger.interest();
+    assert_eq!(posted.len(), 1);
+    assert_eq!((posted[0].posted, posted[0].amount), (10 * DAY, Amount::new(15.0)));
+    assert_eq!(posted[0].accruals.len(), 2);
+    assert_eq!((posted[0].accruals[0].from, posted[0].accruals[0].to), (0, 5 * DAY));
+
+    let (_, accruing) = ledger.accrued_interest().next().ok_or("No interest accruing")?;
+    assert!((accruing[0].interest - 4.03).abs() < 1e-6);
+
+    // the client that came in after the period has not earned anything
+    assert!(engine.get_ledger(2).ok_or("Ledger not found")?.interest().is_empty());
+    Ok(())
+}
+
+#[test]
+fn tiered_and_sharded() -> txnengine::Result<()> {
+    let rule = InterestRule::Tiered(vec![(Amount::new(100.0), 36.5), (Amount::new(0.0), 3.65)]);
+    assert_eq!(rule.rate(Amount::new(50.0)), Some(3.65));
+    assert_eq!(rule.rate(Amount::new(150.0)), Some(36.5));
+
+    let config = EngineConfig::default().with_interest(schedule()?.with_period_days(3)?);
+    let transactions = || (0..40u32).map(|tx| {
+        Transaction::new((tx % 5) as u16, tx, TransactionType::Deposit { amount: Amount::new(100.0 + tx as f32) })
+            .with_timestamp(tx as u64 * DAY / 2)
+    });
+
+    let mut sequential = TransactionEngine::with_config(config.clone());
+    for t in transactions() {
+        sequential.apply(t)?;
+    }
+    let sharded = ShardedEngine::new(3)
+        .with_config(config)
+        .process(transactions().map(|t| ((), t)), |_, _| {})?;
+
+    for balance in sequential.iter() {
+        let ledger = sharded.get_ledger(balance.client()).ok_or("Ledger not found")?;
+        assert_eq!(ledger.get_balance(), balance);
+        assert!(!ledger.interest().is_empty());
+    }
+    Ok(())
+}
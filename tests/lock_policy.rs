@@ -0,0 +1,44 @@
+use txnengine::transaction::{TransactionEngine, Transaction, TransactionType};
+use txnengine::transaction::amount::Amount;
+use txnengine::transaction::config::{EngineConfig, LockPolicy};
+
+fn charged_back(config : EngineConfig) -> txnengine::Result<TransactionEngine> {
+    let mut engine = TransactionEngine::with_config(config);
+    engine.apply(Transaction::new(1, 1, TransactionType::Deposit { amount: Amount::new(10.0) }))?;
+    engine.apply(Transaction::new(1, 2, TransactionType::Deposit { amount: Amount::new(5.0) }))?;
+    engine.apply(Transaction::new(1, 3, TransactionType::Deposit { amount: Amount::new(3.0) }))?;
+    engine.apply(Transaction::new(1, 2, TransactionType::Dispute { amount: None }))?;
+    engine.apply(Transaction::new(1, 3, TransactionType::Dispute { amount: None }))?;
+    engine.apply(Transaction::new(1, 2, TransactionType::ChargeBack { amount: None }))?;
+    Ok(engine)
+}
+
+#[test]
+fn locked_account_blocks_everything_by_default() -> txnengine::Result<()> {
+    let mut engine = charged_back(EngineConfig::default())?;
+
+    assert!(engine.apply(Transaction::new(1, 3, TransactionType::Resolve { amount: None })).is_err());
+    assert!(engine.apply(Transaction::new(1, 4, TransactionType::Deposit { amount: Amount::new(1.0) })).is_err());
+
+    let balance = engine.get_ledger(1).ok_or("Ledger not found")?.get_balance();
+    assert_eq!(balance.held(), 3.0);
+    Ok(())
+}
+
+#[test]
+fn lock_policy_allows_resolution_and_deposits() -> txnengine::Result<()> {
+    let policy = LockPolicy::parse("deposit, resolve,chargeback")?;
+    let mut engine = charged_back(EngineConfig::default().with_lock_policy(policy))?;
+
+    engine.apply(Transaction::new(1, 3, TransactionType::Resolve { amount: None }))?;
+    engine.apply(Transaction::new(1, 4, TransactionType::Deposit { amount: Amount::new(1.0) }))?;
+    assert!(engine.apply(Transaction::new(1, 5, TransactionType::Withdrawal { amount: Amount::new(1.0) })).is_err());
+
+    let balance = engine.get_ledger(1).ok_or("Ledger not found")?.get_balance();
+    assert_eq!(balance.available(), 14.0);
+    assert_eq!(balance.held(), 0.0);
+    assert!(balance.locked());
+
+    assert!(LockPolicy::parse("deposit,unlock").is_err());
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use txnengine::readers::{self, InputOrder, MultiFileReader};
+
+fn write_shard(dir : &Path, name : &str, content : &str) -> PathBuf {
+    let path = dir.join(name);
+    fs::write(&path, content).unwrap();
+    path
+}
+
+#[test]
+fn merged_by_timestamp() -> txnengine::Result<()> {
+    let dir = std::env::temp_dir().join(format!("txnengine-multi-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+
+    write_shard(&dir, "a.csv", "type,client,tx,amount,timestamp\n\
+        deposit,1,1,1.0,100\n\
+        deposit,1,3,1.0,300\n");
+    write_shard(&dir, "b.csv", "type,client,tx,amount,timestamp\n\
+        deposit,2,2,1.0,200\n\
+        bogus,2,9,1.0,250\n\
+        deposit,2,4,1.0,400\n");
+    write_shard(&dir, "ignored.txt", "not a csv");
+
+    let files = readers::expand_inputs(&[dir.to_string_lossy().to_string()])?;
+    assert_eq!(files.len(), 2);
+
+    let merged : Vec<_> = MultiFileReader::new(&files)?
+        .iter(InputOrder::Merged)?
+        .map(|t| (t.transaction.tx, t.provenance.line))
+        .collect();
+    assert_eq!(merged, vec![(1, 2), (2, 2), (3, 3), (4, 4)]);
+
+    let sequential : Vec<_> = MultiFileReader::new(&files)?
+        .iter(InputOrder::Sequential)?
+        .map(|t| t.transaction.tx)
+        .collect();
+    assert_eq!(sequential, vec![1, 3, 2, 4]);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn malformed_row_skipped() -> txnengine::Result<()> {
+    let dir = std::env::temp_dir().join(format!("txnengine-malformed-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+    let file = write_shard(&dir, "a.csv", "type,client,tx,amount\n\
+        deposit,1,1,1.0\n\
+        deposit,1,2,1.0,extra\n\
+        deposit,1,3,1.0\n");
+
+    // the row with too many fields is skipped, the rest of the file is not
+    let reader = MultiFileReader::new(&[file])?;
+    let parse_errors = reader.parse_errors();
+    let read : Vec<_> = reader.iter(InputOrder::Sequential)?.map(|t| t.transaction.tx).collect();
+    assert_eq!(read, vec![1, 3]);
+    assert_eq!(parse_errors.count(), 1);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
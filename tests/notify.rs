@@ -0,0 +1,93 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread;
+use std::time::Duration;
+
+use txnengine::notify::{FileSink, Notifier, WebhookSink};
+use txnengine::transaction::{TransactionEngine, Transaction, TransactionType};
+use txnengine::transaction::amount::Amount;
+
+/// deposits and charges it back, which locks the account of client 1
+fn lock(engine : &mut TransactionEngine) -> txnengine::Result<()> {
+    engine.apply(Transaction::new(1, 1, TransactionType::Deposit { amount: Amount::new(5.0) }))?;
+    engine.apply(Transaction::new(1, 1, TransactionType::Dispute { amount: None }))?;
+    engine.apply(Transaction::new(1, 1, TransactionType::ChargeBack { amount: None }))
+}
+
+/// reads a request and returns its body
+fn read_body(stream : &mut std::net::TcpStream) -> String {
+    let mut reader = BufReader::new(stream);
+    let mut length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+            length = value.trim().parse().unwrap();
+        }
+        if line.trim().is_empty() {
+            break;
+        }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+    String::from_utf8(body).unwrap()
+}
+
+#[test]
+fn webhook_retries() -> txnengine::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let url = format!("http://{}/hooks/locked", listener.local_addr()?);
+
+    // fails the first post and accepts the second
+    let server = thread::spawn(move || {
+        let mut bodies = Vec::new();
+        for status in ["500 Internal Server Error", "200 OK"] {
+            let (mut stream, _) = listener.accept().unwrap();
+            bodies.push(read_body(&mut stream));
+            write!(stream, "HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status).unwrap();
+        }
+        bodies
+    });
+
+    let webhook = WebhookSink::new(vec![url])?.with_retries(2, Duration::from_millis(1));
+    let mut engine = TransactionEngine::new().with_observer(Box::new(Notifier::new(Box::new(webhook))));
+    lock(&mut engine)?;
+
+    let bodies = server.join().map_err(|_| "Server panicked")?;
+    assert_eq!(bodies[0], bodies[1]);
+    let notification : serde_json::Value = serde_json::from_str(&bodies[1])?;
+    assert_eq!(notification["event"], "account_locked");
+    assert_eq!(notification["client"], 1);
+    assert_eq!(bodies[1].matches(r#""client""#).count(), 1);
+    assert_eq!(notification["type"], "chargeback");
+    assert_eq!(notification["locked"], true);
+    Ok(())
+}
+
+#[test]
+fn dead_letter_and_file() -> txnengine::Result<()> {
+    // a port that nothing listens on
+    let url = format!("http://{}", TcpListener::bind("127.0.0.1:0")?.local_addr()?);
+    let dir = std::env::temp_dir();
+    let dead_letter = dir.join(format!("txnengine-dead-letter-{}.jsonl", std::process::id()));
+    let file = dir.join(format!("txnengine-notify-{}.jsonl", std::process::id()));
+
+    let webhook = WebhookSink::new(vec![url.clone()])?
+        .with_retries(1, Duration::from_millis(1))
+        .with_dead_letter(&dead_letter);
+    let mut engine = TransactionEngine::new()
+        .with_observer(Box::new(Notifier::new(Box::new(webhook))))
+        .with_observer(Box::new(Notifier::new(Box::new(FileSink::new(&file)))));
+    lock(&mut engine)?;
+
+    let dead : serde_json::Value = serde_json::from_str(fs::read_to_string(&dead_letter)?.trim())?;
+    let written = fs::read_to_string(&file)?;
+    fs::remove_file(&dead_letter)?;
+    fs::remove_file(&file)?;
+
+    assert_eq!(dead["url"], url.as_str());
+    assert_eq!(dead["payload"].as_str(), Some(written.trim()));
+    assert!(WebhookSink::new(vec!["https://example.com".to_string()]).is_err());
+    Ok(())
+}
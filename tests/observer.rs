@@ -0,0 +1,105 @@
+use std::sync::{Arc, Mutex};
+
+use txnengine::transaction::{TransactionEngine, Transaction, TransactionType};
+use txnengine::transaction::amount::Amount;
+use txnengine::transaction::ledger::ClientBalance;
+use txnengine::transaction::observer::{EngineObserver, ObserverFactory};
+use txnengine::transaction::sharded::ShardedEngine;
+
+/// records every callback as a line
+#[derive(Debug, Clone, Default)]
+struct Recorder {
+    events : Arc<Mutex<Vec<String>>>,
+}
+
+impl Recorder {
+    fn push(&self, event : String) {
+        self.events.lock().unwrap().push(event);
+    }
+
+    fn events(&self) -> Vec<String> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+impl EngineObserver for Recorder {
+    fn on_applied(&mut self, transaction : &Transaction, before : &ClientBalance, after : &ClientBalance) {
+        self.push(format!("applied {} {} -> {}", transaction.tx, before.total(), after.total()));
+    }
+
+    fn on_rejected(&mut self, transaction : &Transaction, _error : &txnengine::Error) {
+        self.push(format!("rejected {}", transaction.tx));
+    }
+
+    fn on_locked(&mut self, transaction : &Transaction, balance : &ClientBalance) {
+        self.push(format!("locked {} {}", transaction.tx, balance.client()));
+    }
+
+    fn on_dispute_opened(&mut self, transaction : &Transaction, _before : &ClientBalance, after : &ClientBalance) {
+        self.push(format!("disputed {} {}", transaction.tx, after.held()));
+    }
+
+    fn on_counterparty_applied(&mut self, transaction : &Transaction, before : &ClientBalance, after : &ClientBalance) {
+        self.push(format!("received {} {} {} -> {}", transaction.tx, after.client(), before.total(), after.total()));
+    }
+}
+
+#[test]
+fn observe_transactions() -> txnengine::Result<()> {
+    let recorder = Recorder::default();
+    let mut engine = TransactionEngine::new().with_observer(Box::new(recorder.clone()));
+    engine.apply(Transaction::new(1, 1, TransactionType::Deposit { amount: Amount::new(5.0) }))?;
+    assert!(engine.apply(Transaction::new(1, 2, TransactionType::Withdrawal { amount: Amount::new(9.0) })).is_err());
+    engine.apply(Transaction::new(1, 1, TransactionType::Dispute { amount: None }))?;
+    engine.apply(Transaction::new(1, 1, TransactionType::ChargeBack { amount: None }))?;
+
+    assert_eq!(recorder.events(), vec![
+        "applied 1 0.0000 -> 5.0000",
+        "rejected 2",
+        "applied 1 5.0000 -> 5.0000",
+        "disputed 1 5.0000",
+        "applied 1 5.0000 -> 0.0000",
+        "locked 1 1",
+    ]);
+
+    // every shard gets its own observer, here all recording into the same list
+    let recorder = Recorder::default();
+    let shared = recorder.clone();
+    ShardedEngine::new(2)
+        .with_observer(ObserverFactory::new(move || Box::new(shared.clone())))
+        .process((1..=4u16).map(|client| ((), Transaction::new(client, client as u32, TransactionType::Deposit { amount: Amount::new(1.0) }))), |_, _| {})?;
+    assert_eq!(recorder.events().len(), 4);
+    Ok(())
+}
+
+#[test]
+fn receiver_told_on_every_shard() -> txnengine::Result<()> {
+    let transactions = || vec![
+        Transaction::new(1, 1, TransactionType::Deposit { amount: Amount::new(5.0) }),
+        Transaction::new(1, 2, TransactionType::Transfer { to_client: 2, amount: Amount::new(3.0) }),
+        Transaction::new(1, 2, TransactionType::Dispute { amount: None }),
+        Transaction::new(1, 3, TransactionType::Transfer { to_client: 4, amount: Amount::new(1.0) }),
+    ];
+
+    let recorder = Recorder::default();
+    let mut engine = TransactionEngine::new().with_observer(Box::new(recorder.clone()));
+    for t in transactions() {
+        engine.apply(t)?;
+    }
+    let mut expected = recorder.events();
+    assert!(expected.contains(&String::from("received 2 2 0.0000 -> 3.0000")));
+    assert!(expected.contains(&String::from("received 2 2 3.0000 -> 3.0000")));
+
+    // client 2 is on another shard than client 1, client 4 on the same one
+    let recorder = Recorder::default();
+    let shared = recorder.clone();
+    ShardedEngine::new(2)
+        .with_observer(ObserverFactory::new(move || Box::new(shared.clone())))
+        .process(transactions().into_iter().map(|t| ((), t)), |_, _| {})?;
+
+    let mut events = recorder.events();
+    events.sort();
+    expected.sort();
+    assert_eq!(events, expected);
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use txnengine::transaction::{TransactionEngine, Transaction, TransactionType};
+use txnengine::transaction::amount::Amount;
+use txnengine::transaction::config::{EngineConfig, LockPolicy};
+use txnengine::transaction::ledger::DisputeState;
+
+#[test]
+fn partial_dispute_and_chargeback() -> txnengine::Result<()> {
+    let mut engine = TransactionEngine::new();
+    engine.apply(Transaction::new(1, 1, TransactionType::Deposit { amount: Amount::new(10.0) }))?;
+
+    engine.apply(serde_json::from_str(r#"{"type": "dispute", "client": 1, "tx": 1, "amount": "4.0"}"#)?)?;
+    engine.apply(Transaction::new(1, 1, TransactionType::Dispute { amount: Some(Amount::new(5.0)) }))?;
+    assert!(engine.apply(Transaction::new(1, 1, TransactionType::Dispute { amount: Some(Amount::new(2.0)) })).is_err());
+
+    engine.apply(Transaction::new(1, 1, TransactionType::Resolve { amount: Some(Amount::new(3.0)) }))?;
+    assert!(engine.apply(Transaction::new(1, 1, TransactionType::ChargeBack { amount: Some(Amount::new(7.0)) })).is_err());
+    engine.apply(Transaction::new(1, 1, TransactionType::ChargeBack { amount: Some(Amount::new(2.0)) }))?;
+
+    let ledger = engine.get_ledger(1).ok_or("Ledger not found")?;
+    let record = ledger.get_record(1).ok_or("Record not found")?;
+    assert_eq!(record.disputed, 4.0);
+    assert_eq!(record.charged_back, 2.0);
+    assert_eq!(record.state, DisputeState::Disputed);
+    assert_eq!(ledger.get_balance().available(), 4.0);
+    assert_eq!(ledger.get_balance().held(), 4.0);
+    assert_eq!(ledger.get_balance().total(), 8.0);
+
+    assert!(serde_json::from_str::<Transaction>(r#"{"type": "resolve", "client": 1, "tx": 1, "amount": "-1"}"#).is_err());
+    Ok(())
+}
+
+#[test]
+fn partial_transfer_dispute() -> txnengine::Result<()> {
+    // the chargeback locks the sender, who can still dispute
+    let mut engine = TransactionEngine::with_config(EngineConfig::default().with_lock_policy(LockPolicy::parse("dispute")?));
+    engine.apply(Transaction::new(1, 1, TransactionType::Deposit { amount: Amount::new(10.0) }))?;
+    engine.apply(Transaction::new(1, 2, TransactionType::Transfer { to_client: 2, amount: Amount::new(6.0) }))?;
+
+    engine.apply(Transaction::new(1, 2, TransactionType::Dispute { amount: Some(Amount::new(2.0)) }))?;
+    engine.apply(Transaction::new(1, 2, TransactionType::ChargeBack { amount: None }))?;
+
+    let sender = engine.get_ledger(1).ok_or("Ledger not found")?;
+    let receiver = engine.get_ledger(2).ok_or("Ledger not found")?;
+    assert_eq!(sender.get_balance().available(), 6.0);
+    assert_eq!(receiver.get_balance().total(), 4.0);
+
+    // what was not charged back can still be disputed
+    assert!(engine.apply(Transaction::new(1, 2, TransactionType::Dispute { amount: Some(Amount::new(5.0)) })).is_err());
+    engine.apply(Transaction::new(1, 2, TransactionType::Dispute { amount: None }))?;
+    let receiver = engine.get_ledger(2).ok_or("Ledger not found")?;
+    assert_eq!(receiver.get_balance().held(), 4.0);
+    Ok(())
+}
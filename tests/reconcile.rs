@@ -0,0 +1,71 @@
+use std::fs;
+
+use txnengine::balances::{self, Balances, Difference, Field};
+use txnengine::transaction::{TransactionEngine, Transaction, TransactionType};
+use txnengine::transaction::amount::Amount;
+use txnengine::transaction::currency::Currency;
+
+fn expected_file(name : &str, contents : &str) -> txnengine::Result<Balances> {
+    let path = std::env::temp_dir().join(format!("txnengine-{}-{}.csv", name, std::process::id()));
+    fs::write(&path, contents)?;
+    let balances = balances::from_csv(path.to_str().ok_or("Invalid path")?);
+    fs::remove_file(&path)?;
+    balances
+}
+
+#[test]
+fn reconcile_against_expected() -> txnengine::Result<()> {
+    let mut engine = TransactionEngine::new();
+    engine.apply(Transaction::new(1, 1, TransactionType::Deposit { amount: Amount::new(10.0) }))?;
+    engine.apply(Transaction::new(1, 2, TransactionType::Deposit { amount: Amount::new(2.5) }).with_currency("EUR".parse()?))?;
+    engine.apply(Transaction::new(2, 3, TransactionType::Deposit { amount: Amount::new(4.0) }))?;
+    engine.apply(Transaction::new(2, 3, TransactionType::Dispute { amount: None }))?;
+    engine.apply(Transaction::new(3, 4, TransactionType::Deposit { amount: Amount::new(1.0) }))?;
+
+    let expected = expected_file("expected", "client,currency,available,held,total,locked\n\
+        1,,10.0000,0.0000,10.0000,false\n\
+        1,EUR,2.49,0.00,2.49,false\n\
+        2,,0.0000,4.0000,4.0000,true\n\
+        4,,1.0000,0.0000,1.0000,false\n")?;
+    let actual = balances::from_engine(&engine);
+
+    let differences = balances::reconcile(&expected, &actual, 0.0);
+    let found : Vec<String> = differences.iter().map(ToString::to_string).collect();
+    assert_eq!(found, vec![
+        "Client 1 EUR available should be 2.49 but is 2.50",
+        "Client 1 EUR total should be 2.49 but is 2.50",
+        "Client 2 default locked should be true but is false",
+        "Client 3 default balance is not expected",
+        "Client 4 default balance is missing",
+    ]);
+    assert!(matches!(differences[0], Difference::Mismatch { field : Field::Available, .. }));
+
+    // within a cent, only the lock and the clients differ
+    let differences = balances::reconcile(&expected, &actual, 0.01);
+    assert_eq!(differences.len(), 3);
+    assert_eq!(differences[0].key(), (2, Currency::DEFAULT));
+
+    // the engine's output reconciles with itself once read back
+    let mut written = csv::Writer::from_writer(vec![]);
+    for balance in engine.iter() {
+        written.serialize(balance)?;
+    }
+    let written = String::from_utf8(written.into_inner()?)?;
+    assert!(balances::reconcile(&expected_file("written", &written)?, &actual, 0.0).is_empty());
+    Ok(())
+}
+
+#[test]
+fn inconsistent_files() -> txnengine::Result<()> {
+    // a total that is not available + held is read as written and reported
+    let mut engine = TransactionEngine::new();
+    engine.apply(Transaction::new(1, 1, TransactionType::Deposit { amount: Amount::new(2.0) }))?;
+    let expected = expected_file("total", "client,currency,available,held,total,locked\n1,,2.0,0.0,3.0,false\n")?;
+    let differences = balances::reconcile(&expected, &balances::from_engine(&engine), 0.0);
+    assert_eq!(differences.len(), 1);
+    assert!(matches!(differences[0], Difference::Mismatch { field : Field::Total, .. }));
+    assert_eq!(differences[0].to_string(), "Client 1 default total should be 3.0000 but is 2.0000");
+
+    assert!(expected_file("duplicate", "client,currency,available,held,total,locked\n1,,1.0,0,1.0,false\n1,,2.0,0,2.0,false\n").is_err());
+    Ok(())
+}
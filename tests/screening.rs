@@ -0,0 +1,75 @@
+use txnengine::transaction::{TransactionEngine, Transaction, TransactionType};
+use txnengine::transaction::amount::Amount;
+use txnengine::transaction::ledger::LedgerError;
+use txnengine::transaction::interest::DAY;
+use txnengine::transaction::screening::{Action, DisputeCount, QuickWithdrawal, Rules, RoundAmountBurst};
+
+#[test]
+fn flag_and_reject() -> txnengine::Result<()> {
+    let mut engine = TransactionEngine::new().with_screening(Box::new(Rules::builtin(Action::Flag)));
+    engine.apply(Transaction::new(1, 1, TransactionType::Deposit { amount: Amount::new(1000.0) }).with_timestamp(100))?;
+    engine.apply(Transaction::new(1, 2, TransactionType::Deposit { amount: Amount::new(1000.0) }).with_timestamp(200))?;
+    engine.apply(Transaction::new(1, 3, TransactionType::Withdrawal { amount: Amount::new(1000.0) }).with_timestamp(300))?;
+    engine.apply(Transaction::new(1, 4, TransactionType::Withdrawal { amount: Amount::new(10.5) }).with_timestamp(400))?;
+
+    // the third round amount, which is also a quick withdrawal, is flagged but applied
+    let flagged = engine.flagged();
+    assert_eq!(flagged.len(), 1);
+    assert_eq!((flagged[0].tx, flagged[0].txn_type), (3, "withdrawal"));
+    assert!(flagged[0].reason.contains("round amounts") && flagged[0].reason.contains("within 100 seconds"));
+    assert_eq!(engine.get_ledger(1).ok_or("Ledger not found")?.get_balance().available(), 989.5);
+
+    let rules = Rules::new()
+        .with_rule(DisputeCount::new(1, Action::Reject))
+        .with_rule(QuickWithdrawal::new(60, Action::Flag));
+    let mut engine = TransactionEngine::new().with_screening(Box::new(rules));
+    for tx in 1..=2 {
+        engine.apply(Transaction::new(1, tx, TransactionType::Deposit { amount: Amount::new(5.0) }))?;
+    }
+    engine.apply(Transaction::new(1, 1, TransactionType::Dispute { amount: None }))?;
+
+    let rejected = engine.apply(Transaction::new(1, 2, TransactionType::Dispute { amount: None })).err().ok_or("Not rejected")?;
+    assert!(matches!(rejected.downcast_ref::<LedgerError>(), Some(LedgerError::Rejected(_))));
+    assert_eq!(engine.get_ledger(1).ok_or("Ledger not found")?.get_balance().held(), 5.0);
+    Ok(())
+}
+
+#[test]
+fn withdrawal_timed_before_deposit() -> txnengine::Result<()> {
+    let rules = Rules::new().with_rule(QuickWithdrawal::new(60, Action::Reject));
+    let mut engine = TransactionEngine::new().with_screening(Box::new(rules));
+    engine.apply(Transaction::new(1, 1, TransactionType::Deposit { amount: Amount::new(100.0) }).with_timestamp(500))?;
+
+    // out of order, so the withdrawal is not within the window of the deposit
+    engine.apply(Transaction::new(1, 2, TransactionType::Withdrawal { amount: Amount::new(100.0) }).with_timestamp(400))?;
+    assert!(engine.flagged().is_empty());
+    assert_eq!(engine.get_ledger(1).ok_or("Ledger not found")?.get_balance().available(), 0.0);
+    Ok(())
+}
+
+#[test]
+fn only_applied_transactions_count() -> txnengine::Result<()> {
+    let rules = Rules::new()
+        .with_rule(QuickWithdrawal::new(60, Action::Flag))
+        .with_rule(DisputeCount::new(1, Action::Reject))
+        .with_rule(RoundAmountBurst::new(Amount::new(1000.0), 3, DAY, Action::Reject));
+    let mut engine = TransactionEngine::new().with_screening(Box::new(rules));
+    let deposit = |tx, amount, at| Transaction::new(1, tx, TransactionType::Deposit { amount: Amount::new(amount) }).with_timestamp(at);
+
+    // the third round amount is rejected, and does not count towards the next
+    engine.apply(deposit(1, 1000.0, 100))?;
+    engine.apply(deposit(2, 1000.0, 200))?;
+    assert!(engine.apply(deposit(3, 1000.0, 300)).is_err());
+    engine.apply(deposit(4, 1000.0, DAY + 150))?;
+
+    // a dispute of an unknown transaction is not counted
+    engine.apply(Transaction::new(1, 99, TransactionType::Dispute { amount: None }))?;
+    engine.apply(Transaction::new(1, 1, TransactionType::Dispute { amount: None }))?;
+
+    // a quick withdrawal that fails is not reported as flagged
+    let withdrawal = Transaction::new(1, 5, TransactionType::Withdrawal { amount: Amount::new(4999.5) }).with_timestamp(DAY + 160);
+    let failed = engine.apply(withdrawal).err().ok_or("Not failed")?;
+    assert!(matches!(failed.downcast_ref::<LedgerError>(), Some(LedgerError::InsufficentFunds { .. })));
+    assert!(engine.flagged().is_empty());
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use txnengine::readers::{InputOrder, MultiFileReader};
+use txnengine::server::TcpServer;
+use txnengine::transaction::{Transaction, TransactionEngine, TransactionType};
+
+#[test]
+fn line_protocol() -> txnengine::Result<()> {
+    let engine = Arc::new(Mutex::new(TransactionEngine::new()));
+    let server = TcpServer::bind("127.0.0.1:0", engine.clone())?;
+    let addr = server.local_addr()?;
+    thread::spawn(move || server.run());
+
+    let stream = TcpStream::connect(addr)?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+
+    let mut send = |line : &str| -> txnengine::Result<String> {
+        writeln!(writer, "{}", line)?;
+        let mut reply = String::new();
+        reader.read_line(&mut reply)?;
+        Ok(reply.trim_end().to_string())
+    };
+
+    assert_eq!(send("deposit,1,1,10.5")?, "OK");
+    assert_eq!(send(r#"{"type":"withdrawal","client":1,"tx":2,"amount":0.5}"#)?, "OK");
+    assert!(send("withdrawal,1,3,100")?.starts_with("ERR"));
+    assert!(send("bogus,1,4,1")?.starts_with("ERR"));
+    assert_eq!(send("deposit,1,5,2.0")?, "OK");
+    assert_eq!(send("type,tx,client")?, "OK");
+    assert_eq!(send("dispute,5,1")?, "OK");
+    assert_eq!(send("BALANCE 1")?, "OK 1,,10.0000,2.0000,12.0000,false");
+    assert!(send("BALANCE 2")?.starts_with("ERR"));
+
+    let engine = engine.lock().unwrap();
+    assert_eq!(engine.get_ledger(1).ok_or("Ledger not found")?.get_balance().held(), 2.0);
+
+    Ok(())
+}
+
+#[test]
+fn text_fields_kept_as_written() -> txnengine::Result<()> {
+    let path = std::env::temp_dir().join(format!("txnengine-text-{}.csv", std::process::id()));
+    fs::write(&path, "type,client,tx,amount,reason\n\
+        adjustment,1,1,1.5,true\n\
+        adjustment,1,2,1.5,001\n")?;
+
+    let reasons : Vec<_> = MultiFileReader::new(std::slice::from_ref(&path))?
+        .iter(InputOrder::Sequential)?
+        .map(|t| match t.transaction.txn_type {
+            TransactionType::Adjustment { reason, .. } => reason,
+            _ => String::new(),
+        })
+        .collect();
+    assert_eq!(reasons, vec!["true", "001"]);
+
+    // json can give a boolean where text is expected
+    let transaction : Transaction = serde_json::from_str(r#"{"type":"adjustment","client":1,"tx":3,"amount":1.5,"reason":false}"#)?;
+    assert!(matches!(transaction.txn_type, TransactionType::Adjustment { reason, .. } if reason == "false"));
+
+    fs::remove_file(&path)?;
+    Ok(())
+}
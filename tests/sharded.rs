@@ -0,0 +1,90 @@
+use txnengine::transaction::{TransactionEngine, Transaction, TransactionType};
+use txnengine::transaction::sharded::ShardedEngine;
+use txnengine::transaction::amount::Amount;
+use txnengine::transaction::config::EngineConfig;
+use txnengine::transaction::interest::{InterestRule, InterestSchedule, DAY};
+
+/// generates a mix of deposits, withdrawals, transfers and disputes over a few clients
+fn generate() -> Vec<Transaction> {
+    let mut transactions = Vec::new();
+    for tx in 10..2000u32 {
+        let txn_type = match tx % 10 {
+            0 => TransactionType::Dispute { amount: None },
+            2 => TransactionType::ChargeBack { amount: None },
+            3 => TransactionType::Resolve { amount: None },
+            6 => TransactionType::Withdrawal { amount: Amount::new((tx % 50) as f32) },
+            7 => TransactionType::Transfer { to_client: (tx % 11) as u16, amount: Amount::new((tx % 5) as f32) },
+            9 => TransactionType::ChargeBack { amount: None },
+            _ => TransactionType::Deposit { amount: Amount::new((tx % 17) as f32 + 0.25) },
+        };
+        // disputes refer back to an earlier deposit or transfer of the same client
+        let id = match tx % 10 {
+            0 => tx - 3,
+            2 => tx - 5,
+            3 => tx - 6,
+            9 => tx - 8,
+            _ => tx,
+        };
+        let client = (id * 7 % 13) as u16;
+        transactions.push(Transaction::new(client, id, txn_type));
+    }
+    transactions
+}
+
+#[test]
+fn same_as_sequential() -> txnengine::Result<()> {
+    let mut sequential = TransactionEngine::new();
+    for t in generate() {
+        let _ = sequential.apply(t);
+    }
+
+    let sharded = ShardedEngine::new(4)
+        .with_channel_capacity(8)
+        .process(generate().into_iter().map(|t| ((), t)), |_, _| {})?;
+
+    assert_eq!(sequential.iter().count(), sharded.iter().count());
+    for balance in sequential.iter() {
+        let ledger = sharded.get_ledger(balance.client()).ok_or("Ledger not found")?;
+        assert_eq!(ledger.get_balance(), balance);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn idle_shard_keeps_time() -> txnengine::Result<()> {
+    let config = EngineConfig::default()
+        .with_interest(InterestSchedule::new(InterestRule::Flat(36.5)).with_period_days(5)?);
+
+    // client 1 is alone on its shard, its hold expires and its interest
+    // accrues while only client 2 moves the clock forward
+    let transactions = || {
+        let mut transactions = vec![
+            Transaction::new(1, 1, TransactionType::Deposit { amount: Amount::new(100.0) }).with_timestamp(0),
+            Transaction::new(1, 2, TransactionType::Hold { amount: Amount::new(60.0), expires: Some(2 * DAY + 100) }),
+        ];
+        for tx in 3..33u32 {
+            let deposit = Transaction::new(2, tx, TransactionType::Deposit { amount: Amount::new(1.0) });
+            transactions.push(deposit.with_timestamp(tx as u64 * DAY / 3));
+        }
+        transactions
+    };
+
+    let mut sequential = TransactionEngine::with_config(config.clone());
+    for t in transactions() {
+        sequential.apply(t)?;
+    }
+    let sharded = ShardedEngine::new(2)
+        .with_config(config)
+        .process(transactions().into_iter().map(|t| ((), t)), |_, _| {})?;
+
+    for client in [1, 2] {
+        let expected = sequential.get_ledger(client).ok_or("Ledger not found")?;
+        let ledger = sharded.get_ledger(client).ok_or("Ledger not found")?;
+        assert_eq!(ledger.get_balance(), expected.get_balance());
+        assert_eq!(ledger.interest(), expected.interest());
+    }
+    assert_eq!(sharded.get_ledger(1).ok_or("Ledger not found")?.get_balance().held(), 0.0);
+
+    Ok(())
+}
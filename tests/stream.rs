@@ -0,0 +1,33 @@
+#![cfg(feature = "async")]
+
+use futures::stream::StreamExt;
+use tokio::io::BufReader;
+
+use txnengine::stream::{self, AsyncCsvReader};
+
+#[tokio::test]
+async fn csv_stream_balances() -> txnengine::Result<()> {
+    let input = "type, client, tx, amount\n\
+        deposit, 1, 1, 10.0\n\
+        deposit, 2, 2, 5.0\n\
+        withdrawal, 1, 3, 20.0\n\
+        bogus, 1, 4, 1.0\n\
+        withdrawal, 1, 5, 2.5\n\
+        dispute, 2, 2,\n";
+
+    let reader = AsyncCsvReader::new(BufReader::new(input.as_bytes()), "input");
+    let transactions = reader.into_stream().map(|t| t.transaction);
+
+    let mut rejected = 0;
+    let engine = stream::process_stream(transactions, |_| rejected += 1).await;
+    assert_eq!(rejected, 1);
+
+    let one = engine.get_ledger(1).ok_or("Ledger not found")?.get_balance();
+    assert_eq!(one.available(), 7.5);
+
+    let two = engine.get_ledger(2).ok_or("Ledger not found")?.get_balance();
+    assert_eq!(two.held(), 5.0);
+    assert_eq!(two.available(), 0.0);
+
+    Ok(())
+}
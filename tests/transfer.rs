@@ -0,0 +1,65 @@
+use txnengine::transaction::{TransactionEngine, Transaction, TransactionType};
+use txnengine::transaction::amount::Amount;
+
+fn transfer(client : u16, tx : u32, to_client : u16, amount : f32) -> Transaction {
+    Transaction::new(client, tx, TransactionType::Transfer { to_client, amount: Amount::new(amount) })
+}
+
+fn balance(engine : &TransactionEngine, client : u16) -> txnengine::Result<(f32, f32, bool)> {
+    let balance = engine.get_ledger(client).ok_or("Ledger not found")?.get_balance();
+    Ok((*balance.available(), *balance.held(), balance.locked()))
+}
+
+#[test]
+fn transfer_is_atomic() -> txnengine::Result<()> {
+    let mut engine = TransactionEngine::new();
+    engine.apply(Transaction::new(1, 1, TransactionType::Deposit { amount: Amount::new(10.0) }))?;
+    engine.apply(Transaction::new(3, 2, TransactionType::Deposit { amount: Amount::new(1.0) }))?;
+    engine.apply(Transaction::new(3, 2, TransactionType::Dispute { amount: None }))?;
+    engine.apply(Transaction::new(3, 2, TransactionType::ChargeBack { amount: None }))?;
+
+    engine.apply(transfer(1, 3, 2, 4.0))?;
+    assert_eq!(balance(&engine, 1)?, (6.0, 0.0, false));
+    assert_eq!(balance(&engine, 2)?, (4.0, 0.0, false));
+
+    // not enough money, a locked receiver and the same tx again leave both sides untouched
+    assert!(engine.apply(transfer(1, 4, 2, 7.0)).is_err());
+    assert!(engine.apply(transfer(1, 5, 3, 1.0)).is_err());
+    assert!(engine.apply(transfer(1, 3, 2, 1.0)).is_err());
+    assert!(engine.apply(transfer(1, 6, 1, 1.0)).is_err());
+    assert_eq!(balance(&engine, 1)?, (6.0, 0.0, false));
+    assert_eq!(balance(&engine, 2)?, (4.0, 0.0, false));
+    assert_eq!(balance(&engine, 3)?, (0.0, 0.0, true));
+
+    // a failed transfer to an unknown client does not create it
+    assert!(engine.apply(transfer(1, 7, 9, 100.0)).is_err());
+    assert!(engine.get_ledger(9).is_none());
+    assert_eq!(engine.iter().count(), 3);
+
+    Ok(())
+}
+
+#[test]
+fn transfer_disputed_as_unit() -> txnengine::Result<()> {
+    let mut engine = TransactionEngine::new();
+    engine.apply(Transaction::new(1, 1, TransactionType::Deposit { amount: Amount::new(10.0) }))?;
+    engine.apply(transfer(1, 2, 2, 4.0))?;
+
+    // only the sender can dispute the transfer
+    assert!(engine.apply(Transaction::new(2, 2, TransactionType::Dispute { amount: None })).is_err());
+    assert!(engine.apply(Transaction::new(1, 2, TransactionType::Resolve { amount: None })).is_err());
+
+    engine.apply(Transaction::new(1, 2, TransactionType::Dispute { amount: None }))?;
+    assert_eq!(balance(&engine, 2)?, (0.0, 4.0, false));
+    assert!(engine.apply(Transaction::new(1, 2, TransactionType::Dispute { amount: None })).is_err());
+
+    engine.apply(Transaction::new(1, 2, TransactionType::Resolve { amount: None }))?;
+    assert_eq!(balance(&engine, 2)?, (4.0, 0.0, false));
+
+    engine.apply(Transaction::new(1, 2, TransactionType::Dispute { amount: None }))?;
+    engine.apply(Transaction::new(1, 2, TransactionType::ChargeBack { amount: None }))?;
+    assert_eq!(balance(&engine, 1)?, (10.0, 0.0, true));
+    assert_eq!(balance(&engine, 2)?, (0.0, 0.0, false));
+
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use std::fs;
+
+use txnengine::readers::{InputOrder, MultiFileReader};
+use txnengine::transaction::TransactionEngine;
+use txnengine::transaction::config::{EngineConfig, WithdrawalDisputes};
+use txnengine::validation::Validation;
+
+#[test]
+fn count_outcomes() -> txnengine::Result<()> {
+    let dir = std::env::temp_dir().join(format!("txnengine-validation-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+    let (a, b) = (dir.join("a.csv"), dir.join("b.csv"));
+    fs::write(&a, "type,client,tx,amount,timestamp\n\
+        deposit,1,1,5.0,100\n\
+        withdrawal,1,2,10.0,200\n\
+        bogus,1,3,1.0,300\n")?;
+    fs::write(&b, "type,client,tx,amount,timestamp\n\
+        deposit,2,4,1.0,150\n\
+        withdrawal,2,5,1.0,250\n\
+        dispute,2,5,,350\n\
+        chargeback,1,1,,400\n\
+        deposit,1,6,1.0,500\n")?;
+
+    let config = EngineConfig::default().with_withdrawal_disputes(WithdrawalDisputes::Rejected);
+    let mut engine = TransactionEngine::with_config(config);
+    let mut errors = Vec::new();
+    let validation = Validation::run(&mut engine, MultiFileReader::new(&[a, b])?, InputOrder::Merged,
+        |provenance, _| errors.push(provenance.line))?;
+    fs::remove_dir_all(&dir)?;
+
+    // the chargeback of a transaction that is not disputed and the disputed
+    // withdrawal are rejected without a kind of their own
+    assert_eq!(validation.applied, 4);
+    assert_eq!(validation.unparsed, 1);
+    assert_eq!(validation.rejected.get("insufficient_funds"), Some(&1));
+    assert_eq!(validation.rejected.get("other"), Some(&2));
+    assert_eq!((validation.rows(), validation.failed()), (8, 4));
+    assert_eq!(validation.reject_rate(), 50.0);
+    assert_eq!(errors.len(), 3);
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use txnengine::transaction::{Origin, TransactionEngine, Transaction, TransactionType};
+use txnengine::transaction::amount::Amount;
+use txnengine::transaction::config::{EngineConfig, WithdrawalDisputes};
+use txnengine::transaction::currency::Currency;
+use txnengine::transaction::fees::{Fee, FeeRule, FeeSchedule};
+use txnengine::transaction::ledger::ClientLedger;
+use txnengine::transaction::rates::RateTable;
+use txnengine::transaction::verify::{self, Invariant};
+
+fn apply_all(engine : &mut TransactionEngine, transactions : Vec<Transaction>) -> txnengine::Result<()> {
+    for t in transactions {
+        engine.apply(t)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn invariants_hold() -> txnengine::Result<()> {
+    let eur : Currency = "EUR".parse()?;
+    let fees = FeeSchedule {
+        withdrawal : Some(Fee::new(FeeRule::Percentage(1.0))),
+        chargeback : Some(Fee::new(FeeRule::Flat(Amount::new(2.5)))),
+        ..FeeSchedule::default()
+    };
+    let rates = RateTable::default().with_rate(Currency::DEFAULT, eur, 0.9, 0)?;
+
+    for disputes in [WithdrawalDisputes::AsDeposit, WithdrawalDisputes::ProvisionalCredit] {
+        let config = EngineConfig::default().with_fees(fees.clone()).with_rates(rates.clone()).with_withdrawal_disputes(disputes);
+        let mut engine = TransactionEngine::with_config(config);
+        apply_all(&mut engine, vec![
+            Transaction::new(1, 1, TransactionType::Deposit { amount: Amount::new(100.0) }),
+            Transaction::new(1, 2, TransactionType::Withdrawal { amount: Amount::new(20.0) }),
+            Transaction::new(1, 3, TransactionType::Transfer { to_client: 2, amount: Amount::new(30.0) }),
+            Transaction::new(1, 4, TransactionType::Hold { amount: Amount::new(5.0), expires: None }),
+            Transaction::new(1, 5, TransactionType::Convert { amount: Amount::new(10.0), to: eur }),
+            Transaction::new(2, 7, TransactionType::Withdrawal { amount: Amount::new(5.0) }),
+            Transaction::new(1, 1, TransactionType::Dispute { amount: Some(Amount::new(15.0)) }),
+            Transaction::new(1, 2, TransactionType::Dispute { amount: None }),
+            Transaction::new(1, 3, TransactionType::Dispute { amount: None }),
+            Transaction::new(1, 2, TransactionType::Resolve { amount: Some(Amount::new(5.0)) }),
+            Transaction::new(1, 3, TransactionType::ChargeBack { amount: Some(Amount::new(10.0)) }),
+            Transaction::new(1, 6, TransactionType::Adjustment { amount: Amount::new(-1.5), reason: "correction".into() }).with_origin(Origin::Operator),
+            Transaction::new(3, 8, TransactionType::Deposit { amount: Amount::new(50.0) }),
+            Transaction::new(3, 9, TransactionType::Withdrawal { amount: Amount::new(10.0) }),
+            Transaction::new(3, 9, TransactionType::Dispute { amount: None }),
+            Transaction::new(3, 9, TransactionType::ChargeBack { amount: None }),
+            Transaction::new(4, 10, TransactionType::Deposit { amount: Amount::new(40.0) }),
+            Transaction::new(4, 10, TransactionType::Dispute { amount: None }),
+            Transaction::new(4, 10, TransactionType::ChargeBack { amount: Some(Amount::new(15.0)) }),
+        ])?;
+
+        assert_eq!(engine.verify(), vec![]);
+    }
+    Ok(())
+}
+
+#[test]
+fn violations_found() -> txnengine::Result<()> {
+    let config = EngineConfig::default();
+    let mut ledger = ClientLedger::new(1);
+    ledger.apply_transaction(&Transaction::new(1, 1, TransactionType::Deposit { amount: Amount::new(10.0) }), &config)?;
+    assert!(verify::verify(&ledger, &config).is_empty());
+
+    // money that came from nowhere, and held that no dispute accounts for
+    ledger.get_balance_mut().deposit(Amount::new(4.0))?;
+    ledger.get_balance_mut().hold(Amount::new(2.0));
+
+    let violations = verify::verify(&ledger, &config);
+    let found : Vec<(Invariant, Amount, Amount)> = violations.iter().map(|v| (v.invariant, v.expected, v.actual)).collect();
+    assert_eq!(found, vec![
+        (Invariant::OpenDisputes, Amount::new(0.0), Amount::new(2.0)),
+        (Invariant::NetFlow, Amount::new(10.0), Amount::new(14.0)),
+    ]);
+    assert_eq!(violations[1].to_string(), "Client 1 total by net flow should be 10.0000 but is 14.0000");
+
+    ledger.get_balance_mut().release(Amount::new(3.0));
+    assert!(verify::verify(&ledger, &config).iter().any(|v| v.invariant == Invariant::NegativeHeld));
+    Ok(())
+}
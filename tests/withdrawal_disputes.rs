@@ -0,0 +1,75 @@
+use txnengine::transaction::{TransactionEngine, Transaction, TransactionType};
+use txnengine::transaction::amount::Amount;
+use txnengine::transaction::config::{EngineConfig, WithdrawalDisputes};
+
+fn engine_with(withdrawal_disputes : WithdrawalDisputes) -> txnengine::Result<TransactionEngine> {
+    let mut engine = TransactionEngine::with_config(EngineConfig::default().with_withdrawal_disputes(withdrawal_disputes));
+    engine.apply(Transaction::new(1, 1, TransactionType::Deposit { amount: Amount::new(10.0) }))?;
+    engine.apply(Transaction::new(1, 2, TransactionType::Withdrawal { amount: Amount::new(4.0) }))?;
+    Ok(engine)
+}
+
+#[test]
+fn deposit_dispute_paths() -> txnengine::Result<()> {
+    let mut engine = engine_with(WithdrawalDisputes::ProvisionalCredit)?;
+
+    engine.apply(Transaction::new(1, 1, TransactionType::Dispute { amount: None }))?;
+    assert!(engine.apply(Transaction::new(1, 1, TransactionType::Dispute { amount: None })).is_err());
+    engine.apply(Transaction::new(1, 1, TransactionType::Resolve { amount: None }))?;
+    assert!(engine.apply(Transaction::new(1, 1, TransactionType::ChargeBack { amount: None })).is_err());
+
+    engine.apply(Transaction::new(1, 1, TransactionType::Dispute { amount: None }))?;
+    {
+        let balance = engine.get_ledger(1).ok_or("Ledger not found")?.get_balance();
+        assert_eq!(balance.available(), -4.0);
+        assert_eq!(balance.held(), 10.0);
+    }
+
+    engine.apply(Transaction::new(1, 1, TransactionType::ChargeBack { amount: None }))?;
+    let balance = engine.get_ledger(1).ok_or("Ledger not found")?.get_balance();
+    assert_eq!(balance.total(), -4.0);
+    assert!(balance.locked());
+    Ok(())
+}
+
+#[test]
+fn withdrawal_dispute_paths() -> txnengine::Result<()> {
+    // disputed like a deposit
+    let mut engine = engine_with(WithdrawalDisputes::AsDeposit)?;
+    engine.apply(Transaction::new(1, 2, TransactionType::Dispute { amount: None }))?;
+    {
+        let balance = engine.get_ledger(1).ok_or("Ledger not found")?.get_balance();
+        assert_eq!(balance.available(), 2.0);
+        assert_eq!(balance.held(), 4.0);
+    }
+
+    // provisional credit, resolved
+    let mut engine = engine_with(WithdrawalDisputes::ProvisionalCredit)?;
+    engine.apply(Transaction::new(1, 2, TransactionType::Dispute { amount: None }))?;
+    {
+        let balance = engine.get_ledger(1).ok_or("Ledger not found")?.get_balance();
+        assert_eq!(balance.available(), 6.0);
+        assert_eq!(balance.held(), 4.0);
+    }
+    engine.apply(Transaction::new(1, 2, TransactionType::Resolve { amount: None }))?;
+    {
+        let balance = engine.get_ledger(1).ok_or("Ledger not found")?.get_balance();
+        assert_eq!(balance.available(), 6.0);
+        assert_eq!(balance.held(), 0.0);
+    }
+
+    // provisional credit, charged back
+    engine.apply(Transaction::new(1, 2, TransactionType::Dispute { amount: None }))?;
+    engine.apply(Transaction::new(1, 2, TransactionType::ChargeBack { amount: None }))?;
+    {
+        let balance = engine.get_ledger(1).ok_or("Ledger not found")?.get_balance();
+        assert_eq!(balance.available(), 10.0);
+        assert_eq!(balance.held(), 0.0);
+        assert!(balance.locked());
+    }
+
+    let mut engine = engine_with(WithdrawalDisputes::Rejected)?;
+    assert!(engine.apply(Transaction::new(1, 2, TransactionType::Dispute { amount: None })).is_err());
+    engine.apply(Transaction::new(1, 1, TransactionType::Dispute { amount: None }))?;
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use std::fs;
+
+use txnengine::transaction::{TransactionEngine, Transaction, TransactionType};
+use txnengine::transaction::amount::Amount;
+use txnengine::transaction::config::EngineConfig;
+use txnengine::transaction::interest::DAY;
+use txnengine::transaction::ledger::LedgerError;
+use txnengine::transaction::limits::{Limit, LimitWindow, WithdrawalLimits};
+
+fn withdraw(engine : &mut TransactionEngine, client : u16, tx : u32, amount : f32, at : u64) -> txnengine::Result<()> {
+    engine.apply(Transaction::new(client, tx, TransactionType::Withdrawal { amount: Amount::new(amount) }).with_timestamp(at))
+}
+
+fn limit_of(result : txnengine::Result<()>) -> Option<(Limit, Option<LimitWindow>)> {
+    match result.err()?.downcast_ref::<LedgerError>() {
+        Some(LedgerError::LimitExceeded { limit, window }) => Some((*limit, *window)),
+        _ => None,
+    }
+}
+
+#[test]
+fn limits_from_csv() -> txnengine::Result<()> {
+    let path = std::env::temp_dir().join(format!("txnengine-limits-{}.csv", std::process::id()));
+    fs::write(&path, "client,max_single,max_total,max_count,window\n\
+        ,100,150,,daily\n\
+        2,,,2,3600\n")?;
+    let limits = WithdrawalLimits::from_csv(path.to_str().ok_or("Invalid path")?)?;
+    fs::remove_file(&path)?;
+
+    let mut engine = TransactionEngine::with_config(EngineConfig::default().with_withdrawal_limits(limits));
+    for client in 1..=2 {
+        engine.apply(Transaction::new(client, client as u32, TransactionType::Deposit { amount: Amount::new(1000.0) }))?;
+    }
+
+    // the default limits of client 1, by calendar day
+    assert_eq!(limit_of(withdraw(&mut engine, 1, 10, 120.0, 0)), Some((Limit::Single(Amount::new(100.0)), None)));
+    withdraw(&mut engine, 1, 11, 100.0, 10)?;
+    assert_eq!(limit_of(withdraw(&mut engine, 1, 12, 60.0, DAY - 1)), Some((Limit::Total(Amount::new(150.0)), Some(LimitWindow::Daily))));
+    withdraw(&mut engine, 1, 13, 60.0, DAY)?;
+
+    // client 2 has its own limit of two withdrawals in a rolling hour
+    withdraw(&mut engine, 2, 20, 500.0, DAY)?;
+    withdraw(&mut engine, 2, 21, 10.0, DAY + 1800)?;
+    assert_eq!(limit_of(withdraw(&mut engine, 2, 22, 10.0, DAY + 3599)), Some((Limit::Count(2), Some(LimitWindow::Rolling(3600)))));
+    withdraw(&mut engine, 2, 23, 10.0, DAY + 3600)?;
+
+    assert_eq!(engine.get_ledger(1).ok_or("Ledger not found")?.get_balance().available(), 840.0);
+    assert_eq!(engine.get_ledger(2).ok_or("Ledger not found")?.get_balance().available(), 480.0);
+    Ok(())
+}
+
+#[test]
+fn untimed_withdrawals_and_transfers() -> txnengine::Result<()> {
+    let path = std::env::temp_dir().join(format!("txnengine-untimed-limits-{}.csv", std::process::id()));
+    fs::write(&path, "client,max_single,max_total,max_count,window\n,100,150,,daily\n2,100,,,daily\n")?;
+    let limits = WithdrawalLimits::from_csv(path.to_str().ok_or("Invalid path")?)?;
+    fs::remove_file(&path)?;
+
+    let mut engine = TransactionEngine::with_config(EngineConfig::default().with_withdrawal_limits(limits));
+    engine.apply(Transaction::new(1, 1, TransactionType::Deposit { amount: Amount::new(1000.0) }))?;
+    engine.apply(Transaction::new(2, 2, TransactionType::Deposit { amount: Amount::new(1000.0) }))?;
+
+    // without a time, a total limit cannot be checked, while a single limit can
+    let untimed = |client, tx, amount| Transaction::new(client, tx, TransactionType::Withdrawal { amount: Amount::new(amount) });
+    assert!(engine.apply(untimed(1, 3, 50.0)).is_err());
+    assert_eq!(limit_of(engine.apply(untimed(2, 4, 120.0))), Some((Limit::Single(Amount::new(100.0)), None)));
+    engine.apply(untimed(2, 5, 100.0))?;
+
+    // a transfer out counts towards the same limits as a withdrawal
+    let transfer = |tx, amount, at| Transaction::new(1, tx, TransactionType::Transfer { to_client: 2, amount: Amount::new(amount) }).with_timestamp(at);
+    engine.apply(transfer(6, 100.0, DAY))?;
+    assert_eq!(limit_of(withdraw(&mut engine, 1, 7, 60.0, DAY + 10)), Some((Limit::Total(Amount::new(150.0)), Some(LimitWindow::Daily))));
+    assert_eq!(limit_of(engine.apply(transfer(8, 60.0, DAY + 20))), Some((Limit::Total(Amount::new(150.0)), Some(LimitWindow::Daily))));
+    withdraw(&mut engine, 1, 9, 50.0, DAY + 30)?;
+
+    assert_eq!(engine.get_ledger(1).ok_or("Ledger not found")?.get_balance().available(), 850.0);
+    assert_eq!(engine.get_ledger(2).ok_or("Ledger not found")?.get_balance().available(), 1000.0);
+    Ok(())
+}